@@ -0,0 +1,26 @@
+use std::{env, path::PathBuf};
+
+// The embedded-hashlist feature builds in hash_list.hmla from the crate root, or
+// the file TONYTOOLS_HASH_LIST points to. The list isn't part of the repo, so
+// without one the feature still builds and HashList::embedded gives None.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(embedded_hashlist)");
+    println!("cargo:rerun-if-env-changed=TONYTOOLS_HASH_LIST");
+
+    if env::var_os("CARGO_FEATURE_EMBEDDED_HASHLIST").is_none() {
+        return;
+    }
+
+    let path = match env::var_os("TONYTOOLS_HASH_LIST") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("hash_list.hmla"),
+    };
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    if path.is_file() {
+        println!("cargo:rustc-cfg=embedded_hashlist");
+        println!("cargo:rustc-env=TONYTOOLS_HASH_LIST_PATH={}", path.display());
+    } else {
+        println!("cargo:warning=No hash list at {}, nothing will be embedded", path.display());
+    }
+}
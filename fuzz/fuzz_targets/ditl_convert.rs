@@ -0,0 +1,20 @@
+#![no_main]
+
+use bimap::BiMap;
+use libfuzzer_sys::fuzz_target;
+use tonytools::{ditl::DITL, hashlist::HashList};
+
+// A couple of depends for the soundtags to reference.
+const META: &str = r#"{"hash_offset":0,"hash_reference_data":[{"hash":"00C0000000000000","flag":"1F"},{"hash":"00D0000000000000","flag":"1F"}],"hash_reference_table_dummy":0,"hash_reference_table_size":22,"hash_resource_type":"DITL","hash_size":0,"hash_size_final":0,"hash_size_in_memory":0,"hash_size_in_video_memory":0,"hash_value":"00E0000000000000"}"#;
+
+fuzz_target!(|data: &[u8]| {
+    let hashlist = HashList {
+        tags: BiMap::new(),
+        switches: BiMap::new(),
+        lines: BiMap::new(),
+        version: 0,
+    };
+
+    let ditl = DITL::new(hashlist).unwrap();
+    let _ = ditl.convert(data, META.to_string());
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use bimap::BiMap;
+use libfuzzer_sys::fuzz_target;
+use tonytools::{
+    dlge::{WeightFormat, DLGE},
+    hashlist::HashList,
+    Version,
+};
+
+// Enough depends for the DITL, CLNG and a couple of wav/ffx references.
+const META: &str = r#"{"hash_offset":0,"hash_reference_data":[{"hash":"00A0000000000000","flag":"1F"},{"hash":"00B0000000000000","flag":"1F"},{"hash":"00C0000000000000","flag":"81"},{"hash":"00D0000000000000","flag":"81"}],"hash_reference_table_dummy":0,"hash_reference_table_size":40,"hash_resource_type":"DLGE","hash_size":0,"hash_size_final":0,"hash_size_in_memory":0,"hash_size_in_video_memory":0,"hash_value":"00E0000000000000"}"#;
+
+fuzz_target!(|data: &[u8]| {
+    for version in [Version::H2016, Version::H2, Version::H3] {
+        let hashlist = HashList {
+            tags: BiMap::new(),
+            switches: BiMap::new(),
+            lines: BiMap::new(),
+            version: 0,
+        };
+
+        let dlge = DLGE::new(hashlist, version, None, None, WeightFormat::default()).unwrap();
+        let _ = dlge.convert(data, META.to_string());
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tonytools::hashlist::HashList;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = HashList::load(data);
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use bimap::BiMap;
+use libfuzzer_sys::fuzz_target;
+use tonytools::{hashlist::HashList, locr::LOCR, Version};
+
+const META: &str = r#"{"hash_offset":0,"hash_reference_data":[],"hash_reference_table_dummy":0,"hash_reference_table_size":4,"hash_resource_type":"LOCR","hash_size":0,"hash_size_final":0,"hash_size_in_memory":0,"hash_size_in_video_memory":0,"hash_value":"00E0000000000000"}"#;
+
+fuzz_target!(|data: &[u8]| {
+    for version in [Version::H2016, Version::H2, Version::H3] {
+        for symmetric in [false, true] {
+            let hashlist = HashList {
+                tags: BiMap::new(),
+                switches: BiMap::new(),
+                lines: BiMap::new(),
+                version: 0,
+            };
+
+            let locr = LOCR::new(hashlist, version, None, symmetric).unwrap();
+            let _ = locr.convert(data, META.to_string());
+        }
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tonytools::{rtlv::RTLV, Version};
+
+const META: &str = r#"{"hash_offset":0,"hash_reference_data":[],"hash_reference_table_dummy":0,"hash_reference_table_size":4,"hash_resource_type":"RTLV","hash_size":0,"hash_size_final":0,"hash_size_in_memory":0,"hash_size_in_video_memory":0,"hash_value":"00E0000000000000"}"#;
+
+fuzz_target!(|data: &[u8]| {
+    let rtlv = RTLV::new(Version::H3, None).unwrap();
+    let _ = rtlv.convert(data, META.to_string());
+});
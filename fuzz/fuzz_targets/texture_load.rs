@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tonytools::{
+    hmtextures::{structs::Tony, Texture},
+    Version,
+};
+
+// Loads the input as a TEXT of each game, and anything that loads is decoded too.
+fuzz_target!(|data: &[u8]| {
+    for version in [Version::H2016, Version::H2, Version::H3] {
+        if let Ok(image) = Texture::load(version, data, None) {
+            let _ = Tony::try_from(image);
+        }
+    }
+});
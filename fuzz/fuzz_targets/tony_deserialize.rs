@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tonytools::hmtextures::structs::Tony;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Tony::deserialize(data);
+});
@@ -1,21 +1,48 @@
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use serde_json::Map;
 
-use super::{LangError, LangResult, Rebuilt};
-use crate::util::{
-    rpkg::{self, ResourceMeta},
-    vec_of_strings,
+use super::{
+    check_min_size, default_lang_map, normalize, parse_json, parse_meta, LangError, LangResult,
+    Rebuilt, ToWriter,
 };
+use crate::util::rpkg::ResourceMeta;
 use crate::Version;
 use bitchomp::{ByteReader, ByteWriter, ChompFlatten, Endianness};
 
+// One flag per language, with at least one language.
+const MIN_SIZE: usize = 1;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ClngJson {
     #[serde(rename = "$schema")]
     schema: String,
     hash: String,
-    languages: Map<String, serde_json::Value>,
+    languages: IndexMap<String, bool>,
+}
+
+impl ToWriter for ClngJson {}
+
+impl ClngJson {
+    pub fn enabled_languages(&self) -> Vec<String> {
+        self.languages
+            .iter()
+            .filter(|(_, enabled)| **enabled)
+            .map(|(lang, _)| lang.clone())
+            .collect()
+    }
+
+    pub fn disabled_languages(&self) -> Vec<String> {
+        self.languages
+            .iter()
+            .filter(|(_, enabled)| !**enabled)
+            .map(|(lang, _)| lang.clone())
+            .collect()
+    }
+
+    // New languages are added at the end, so at the next index in the file.
+    pub fn set(&mut self, lang: &str, enabled: bool) {
+        self.languages.insert(lang.to_string(), enabled);
+    }
 }
 
 pub struct CLNG {
@@ -27,31 +54,23 @@ impl CLNG {
         let lang_map = if let Some(map) = lang_map {
             map
         } else {
-            match version {
-                Version::H2016 | Version::H2 => vec_of_strings![
-                    "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp", "tc"
-                ],
-                Version::H3 => {
-                    vec_of_strings!["xx", "en", "fr", "it", "de", "es", "ru", "cn", "tc", "jp"]
-                }
-                _ => return Err(LangError::UnsupportedVersion),
-            }
+            default_lang_map(version)?
         };
 
         Ok(CLNG { lang_map })
     }
 
     pub fn convert(&self, data: &[u8], meta_json: String) -> LangResult<ClngJson> {
+        let meta = parse_meta(&meta_json, data)?;
         let mut buf = ByteReader::new(data, Endianness::Little);
 
         let mut j = ClngJson {
             schema: "https://tonytools.win/schemas/clng.schema.json".into(),
             hash: "".into(),
-            languages: Map::new(),
+            languages: IndexMap::new(),
         };
 
         let bools = buf.read_n::<u8>(buf.len())?.flatten();
-        let meta: rpkg::ResourceMeta = serde_json::from_str(meta_json.as_str())?;
         j.hash = meta.hash_path.unwrap_or(meta.hash_value);
 
         for i in 0..bools.len() {
@@ -59,32 +78,67 @@ impl CLNG {
                 return Err(LangError::InvalidLanguageMap);
             }
             let lang = self.lang_map.get(i).unwrap();
-            j.languages.insert(lang.clone(), (*bools.get(i).unwrap() == 1u8).into());
+            j.languages.insert(lang.clone(), *bools.get(i).unwrap() == 1u8);
         }
 
         Ok(j)
     }
 
+    pub fn normalize(&self, json: &str) -> LangResult<String> {
+        normalize::<ClngJson>(json)
+    }
+
     pub fn rebuild(&self, json: String) -> LangResult<Rebuilt> {
-        let json: ClngJson = serde_json::from_str(&json)?;
+        let json: ClngJson = parse_json(&json)?;
         let mut buf = ByteWriter::new(Endianness::Little);
 
-        for v in json.languages.values() {
-            if !v.is_boolean() {
-                return Err(LangError::InvalidInput);
-            }
-
-            buf.append(v.as_bool().unwrap() as u8);
+        for enabled in json.languages.values() {
+            buf.append(*enabled as u8);
         }
 
-        Ok(Rebuilt {
-            file: buf.buf(),
-            meta: serde_json::to_string(&ResourceMeta::new(
+        check_min_size(buf.len(), MIN_SIZE)?;
+
+        Rebuilt::new(
+            buf.buf(),
+            &ResourceMeta::new(
                 json.hash,
                 buf.len() as u32,
                 "CLNG".into(),
                 IndexMap::new(),
-            ))?,
-        })
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let json = r#"{
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "languages": {"xx": false, "en": true, "fr": true, "it": false}
+        }"#;
+
+        let clng = CLNG::new(Version::H3, None).unwrap();
+        let rebuilt = clng.rebuild(json.to_string()).unwrap();
+        assert_eq!(rebuilt.file, [0, 1, 1, 0]);
+
+        let json = clng.convert(&rebuilt.file, rebuilt.meta).unwrap();
+        assert_eq!(json.hash, "00123456789ABCDE");
+        assert_eq!(json.enabled_languages(), ["en", "fr"]);
+        assert_eq!(json.disabled_languages(), ["xx", "it"]);
+    }
+
+    #[test]
+    fn non_boolean_languages_are_rejected() {
+        let json = r#"{"$schema": "", "hash": "", "languages": {"xx": 1}}"#;
+        let clng = CLNG::new(Version::H3, None).unwrap();
+        assert!(matches!(
+            clng.rebuild(json.to_string()),
+            Err(LangError::JsonError(_))
+        ));
     }
 }
@@ -1,22 +1,37 @@
+use std::collections::HashMap;
+
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use serde_json::Map;
-
-use super::hashlist::HashList;
-use super::{LangResult, Rebuilt};
-use crate::util::rpkg::{self, ResourceMeta};
+use log::warn;
+use serde_json::{Map, Value};
+
+use super::hashlist::{resolve_hash, CollisionPolicy, HashList};
+use super::{
+    check_min_size, depend_flag, normalize, parse_json, parse_meta, record_depend_flag, LangError,
+    LangResult, Rebuilt, ToWriter,
+};
+use crate::util::hex::u32_to_hex;
+use crate::util::rpkg::ResourceMeta;
 use bitchomp::{ByteReader, ByteWriter, Endianness, ChompFlatten};
 
+// Just the soundtag count, a DITL can have no soundtags.
+const MIN_SIZE: usize = 4;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DitlJson {
     #[serde(rename = "$schema")]
     schema: String,
     hash: String,
     soundtags: Map<String, serde_json::Value>,
+    #[serde(rename = "dependFlags", skip_serializing_if = "Option::is_none")]
+    depend_flags: Option<Map<String, serde_json::Value>>,
 }
 
+impl ToWriter for DitlJson {}
+
 pub struct DITL {
     hashlist: HashList,
+    collision_policy: CollisionPolicy,
     // This is used for rebuilding.
     depends: IndexMap<String, String>,
 }
@@ -25,35 +40,60 @@ impl DITL {
     pub fn new(hashlist: HashList) -> LangResult<Self> {
         Ok(DITL {
             hashlist,
+            collision_policy: CollisionPolicy::default(),
             depends: IndexMap::new(),
         })
     }
 
+    // Overrides take precedence over the hash list in both convert and rebuild.
+    pub fn with_overrides(mut self, overrides: HashMap<u32, String>) -> Self {
+        for (hash, name) in overrides {
+            self.hashlist.tags.insert(hash, name);
+        }
+        self
+    }
+
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
     pub fn convert(&self, data: &[u8], meta_json: String) -> LangResult<DitlJson> {
+        let meta = parse_meta(&meta_json, data)?;
         let mut buf = ByteReader::new(data, Endianness::Little);
 
         let mut j = DitlJson {
             schema: "https://tonytools.win/schemas/ditl.schema.json".into(),
             hash: "".into(),
             soundtags: Map::new(),
+            depend_flags: None,
         };
 
         let count = buf.read::<u32>()?.inner();
-        let hashes = buf.read_n::<u32>((count * 2) as usize)?.flatten(); // Hashes and depend index
-        let meta: rpkg::ResourceMeta = serde_json::from_str(meta_json.as_str())?;
+        let count = count.checked_mul(2).ok_or(LangError::InvalidInput)?;
+        let hashes = buf.read_n::<u32>(count as usize)?.flatten(); // Hashes and depend index
         j.hash = meta.hash_path.unwrap_or(meta.hash_value);
 
+        let mut depend_flags = Map::new();
         for i in (0..hashes.len()).step_by(2) {
             let index = *hashes.get(i).unwrap();
             let hash = *hashes.get(i + 1).unwrap();
-            let depend = meta
-                .hash_reference_data
-                .get(index as usize)
-                .unwrap()
-                .clone();
-            let hex: String = format!("{:08X}", hash);
+            let hex = u32_to_hex(hash);
             let hash = self.hashlist.tags.get_by_left(&hash).unwrap_or(&hex);
-            j.soundtags.insert(hash.clone(), depend.hash.into());
+
+            // A depend table shorter than the file expects keeps the raw index as a
+            // number, rebuild refuses it rather than pointing it at another depend.
+            let Some(depend) = meta.hash_reference_data.get(index as usize) else {
+                warn!("Soundtag {} in {} references missing depend {}", hash, j.hash, index);
+                j.soundtags.insert(hash.clone(), index.into());
+                continue;
+            };
+            record_depend_flag(&mut depend_flags, Some(depend), "1F");
+            j.soundtags.insert(hash.clone(), depend.hash.clone().into());
+        }
+
+        if !depend_flags.is_empty() {
+            j.depend_flags = Some(depend_flags);
         }
 
         Ok(j)
@@ -68,31 +108,97 @@ impl DITL {
         }
     }
 
+    pub fn normalize(&self, json: &str) -> LangResult<String> {
+        normalize::<DitlJson>(json)
+    }
+
     pub fn rebuild(&mut self, json: String) -> LangResult<Rebuilt> {
         self.depends.clear();
-        let json: DitlJson = serde_json::from_str(&json)?;
+        let json: DitlJson = parse_json(&json)?;
 
         let mut buf = ByteWriter::new(Endianness::Little);
 
         buf.append(json.soundtags.len() as u32);
 
         for (tag, hash) in json.soundtags {
-            let hash = hash.as_str().unwrap();
-
-            buf.append(self.add_depend(hash.to_string(), "1F".into()));
-            buf.append(*self.hashlist.tags.get_by_right(&tag).unwrap_or(
-                &u32::from_str_radix(&tag, 16).unwrap_or(crc32fast::hash(tag.as_bytes())),
-            ));
+            let hash = match hash {
+                Value::String(hash) => hash,
+                Value::Number(_) => return Err(LangError::UnresolvedDepend { tag }),
+                _ => return Err(LangError::InvalidInput),
+            };
+
+            let flag = depend_flag(&json.depend_flags, &hash, "1F".into());
+            buf.append(self.add_depend(hash, flag));
+            buf.append(resolve_hash(
+                &self.hashlist.tags,
+                &tag,
+                self.collision_policy,
+            )?);
         }
 
-        Ok(Rebuilt {
-            file: buf.buf(),
-            meta: serde_json::to_string(&ResourceMeta::new(
+        check_min_size(buf.len(), MIN_SIZE)?;
+
+        Rebuilt::new(
+            buf.buf(),
+            &ResourceMeta::new(
                 json.hash,
                 buf.len() as u32,
                 "DITL".into(),
                 self.depends.clone(),
-            ))?,
-        })
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bimap::BiMap;
+    use serde_json::json;
+
+    fn ditl() -> DITL {
+        let hashlist = HashList {
+            tags: BiMap::from_iter([(1, "Tag".to_string())]),
+            switches: BiMap::new(),
+            lines: BiMap::new(),
+            version: 1,
+        };
+        DITL::new(hashlist).unwrap()
+    }
+
+    #[test]
+    fn short_depend_tables_keep_the_index() {
+        let json = json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "soundtags": {"Tag": "00C0000000000001", "00000002": "00C0000000000002"}
+        });
+        let rebuilt = ditl().rebuild(json.to_string()).unwrap();
+
+        // Drop the second soundtag's depend from the meta.
+        let mut meta: Value = serde_json::from_str(&rebuilt.meta).unwrap();
+        meta["hash_reference_data"].as_array_mut().unwrap().pop();
+        let converted = ditl().convert(&rebuilt.file, meta.to_string()).unwrap();
+        assert_eq!(
+            Value::Object(converted.soundtags.clone()),
+            json!({"Tag": "00C0000000000001", "00000002": 1})
+        );
+
+        let json = serde_json::to_string(&converted).unwrap();
+        assert!(matches!(
+            ditl().rebuild(json),
+            Err(LangError::UnresolvedDepend { tag }) if tag == "00000002"
+        ));
+    }
+
+    #[test]
+    fn oversized_counts_are_an_error() {
+        let json = r#"{"$schema": "", "hash": "00123456789ABCDE", "soundtags": {}}"#;
+        let rebuilt = ditl().rebuild(json.to_string()).unwrap();
+
+        assert!(matches!(
+            ditl().convert(&[0xFF; 4], rebuilt.meta),
+            Err(LangError::InvalidInput)
+        ));
     }
 }
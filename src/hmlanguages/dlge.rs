@@ -1,17 +1,31 @@
 use std::borrow::BorrowMut;
-
-use super::Rebuilt;
-use super::{hashlist::HashList, LangError, LangResult};
-use crate::util::cipher::{xtea_decrypt, xtea_encrypt};
-use crate::util::rpkg::{self, is_valid_hash, ResourceMeta};
+use std::collections::HashMap;
+
+use super::{
+    check_min_size, depend_flag, parse_json, parse_meta, record_depend_flag, LineDiff, Rebuilt,
+    StringPolicy, ToWriter,
+};
+use super::{
+    hashlist::{lookup_name, resolve_hash_name, CollisionPolicy, HashList, HashName},
+    LangError, LangResult,
+};
+use crate::util::cipher::{xtea_decrypt_raw, xtea_encrypt};
+use crate::util::csv;
+use crate::util::hex::{u32_from_hex, u32_to_hex};
+use crate::util::reader::ByteReaderExt;
+use crate::util::rpkg::{is_valid_hash, normalize_depend, ResourceMeta};
 use crate::util::vec_of_strings;
 use crate::Version;
-use bitchomp::{ByteReader, ByteWriter, Endianness, ChompFlatten};
+use bitchomp::{ByteReader, ByteWriter, Endianness};
 use fancy_regex::Regex;
 use indexmap::IndexMap;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map};
 
+// The DITL and CLNG indices, a wav with no languages and the root reference.
+const MIN_SIZE: usize = 0x13;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DlgeJson {
     #[serde(rename = "$schema")]
@@ -25,36 +39,47 @@ pub struct DlgeJson {
     langmap: Option<String>,
     #[serde(rename = "rootContainer")]
     root: DlgeType,
+    #[serde(rename = "dependFlags", skip_serializing_if = "Option::is_none")]
+    depend_flags: Option<Map<String, serde_json::Value>>,
 }
 
+impl ToWriter for DlgeJson {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WavFile {
     #[serde(rename = "wavName")]
     wav_name: String,
+    // Only used in switches, where it's required. An empty list is valid but rarely
+    // intended, see DLGE::with_strict_cases.
     #[serde(skip_serializing_if = "Option::is_none")]
-    cases: Option<Vec<String>>,
+    cases: Option<Vec<HashName>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     weight: Option<serde_json::Value>,
-    soundtag: String,
+    // Tag-less wavs have a zero hash, an empty or "0" tag also writes zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    soundtag: Option<HashName>,
     #[serde(rename = "defaultWav")]
     default_wav: Option<String>,
     #[serde(rename = "defaultFfx")]
     default_ffx: Option<String>,
+    // The subtitle, or a {wav, ffx, subtitle} object. The format only has room for
+    // one entry per language, variants are separate wavs in a Random or Switch,
+    // so anything else like an array is an error on rebuild.
     languages: Map<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Random {
     #[serde(skip_serializing_if = "Option::is_none")]
-    cases: Option<Vec<String>>,
+    cases: Option<Vec<HashName>>,
     containers: Vec<DlgeType>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Switch {
     #[serde(rename = "switchKey")]
-    switch_key: String,
-    default: String,
+    switch_key: HashName,
+    default: HashName,
     containers: Vec<DlgeType>,
 }
 
@@ -73,6 +98,271 @@ pub enum DlgeType {
     Null,
 }
 
+impl DlgeJson {
+    // Returns every subtitle as (wav name, language, text).
+    pub fn flatten(&self) -> Vec<(String, String, String)> {
+        let mut lines = Vec::new();
+        self.root.flatten(&mut lines);
+        lines
+    }
+
+    // Subtitles added, removed or changed in other, by wav name and language.
+    pub fn diff(&self, other: &DlgeJson) -> LineDiff {
+        LineDiff::new(self.flatten(), other.flatten())
+    }
+
+    // Writes every subtitle as a wav_name,language,subtitle CSV for spreadsheet
+    // editing. Wav names aren't unique, so there's no merging these back.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        csv::write_row(&mut csv, &["wav_name", "language", "subtitle"]);
+
+        for (wav, language, subtitle) in self.flatten() {
+            csv::write_row(&mut csv, &[&wav, &language, &subtitle]);
+        }
+
+        csv
+    }
+
+    // Renders the container tree as a Graphviz graph for seeing how complex
+    // dialogue fits together. Switch edges are labeled with their cases, random
+    // ones with the weight and sequence ones with the play order.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph DLGE {\n    node [shape=box];\n");
+        self.root.to_dot(&mut dot, &mut 0);
+        dot.push_str("}\n");
+        dot
+    }
+
+    // The container type at the root, as written in the JSON.
+    pub fn root_type(&self) -> &'static str {
+        match self.root {
+            DlgeType::WavFile(_) => "WavFile",
+            DlgeType::Random(_) => "Random",
+            DlgeType::Switch(_) => "Switch",
+            DlgeType::Sequence(_) => "Sequence",
+            DlgeType::Null => "Null",
+        }
+    }
+
+    // A copy with every subtitle emptied, for starting a new translation.
+    // Everything else, including the per-language wav and ffx, is kept.
+    pub fn template(&self) -> DlgeJson {
+        let mut json = self.clone();
+        json.root.template();
+        json
+    }
+
+    // Moves every wav's languages from one game's language map to another's.
+    // Languages the target game doesn't have are dropped with a warning.
+    pub fn remap(&self, from: Version, to: Version) -> LangResult<DlgeJson> {
+        let from_map = match &self.langmap {
+            Some(langmap) => langmap.split(',').map(|s| s.to_string()).collect(),
+            None => default_lang_map(from)?,
+        };
+        let to_map = default_lang_map(to)?;
+
+        let mut json = self.clone();
+        json.langmap = None;
+        let mut dropped = Vec::new();
+        json.root.remap(&from_map, &to_map, &mut dropped)?;
+        if !dropped.is_empty() {
+            warn!(
+                "Languages {} aren't in the target game and were dropped",
+                dropped.join(", ")
+            );
+        }
+
+        Ok(json)
+    }
+}
+
+impl DlgeType {
+    // Languages that had to be dropped are added to dropped, once each.
+    fn remap(
+        &mut self,
+        from: &[String],
+        to: &[String],
+        dropped: &mut Vec<String>,
+    ) -> LangResult<()> {
+        match self {
+            DlgeType::WavFile(wav) => {
+                if wav.languages.keys().any(|lang| !from.contains(lang)) {
+                    return Err(LangError::InvalidLanguageMap);
+                }
+
+                for lang in wav.languages.keys() {
+                    if !to.contains(lang) && !dropped.contains(lang) {
+                        dropped.push(lang.clone());
+                    }
+                }
+
+                let mut languages = Map::new();
+                for lang in to {
+                    if let Some(value) = wav.languages.get(lang) {
+                        languages.insert(lang.clone(), value.clone());
+                    }
+                }
+                wav.languages = languages;
+            }
+            DlgeType::Random(random) => {
+                for child in random.containers.iter_mut() {
+                    child.remap(from, to, dropped)?;
+                }
+            }
+            DlgeType::Switch(switch) => {
+                for child in switch.containers.iter_mut() {
+                    child.remap(from, to, dropped)?;
+                }
+            }
+            DlgeType::Sequence(sequence) => {
+                for child in sequence.containers.iter_mut() {
+                    child.remap(from, to, dropped)?;
+                }
+            }
+            DlgeType::Null => {}
+        }
+
+        Ok(())
+    }
+
+    fn template(&mut self) {
+        match self {
+            DlgeType::WavFile(wav) => {
+                for value in wav.languages.values_mut() {
+                    match value {
+                        serde_json::Value::Object(obj) => {
+                            if obj.contains_key("subtitle") {
+                                obj.insert("subtitle".into(), "".into());
+                            }
+                        }
+                        value => *value = "".into(),
+                    }
+                }
+            }
+            DlgeType::Random(random) => random.containers.iter_mut().for_each(|c| c.template()),
+            DlgeType::Switch(switch) => switch.containers.iter_mut().for_each(|c| c.template()),
+            DlgeType::Sequence(sequence) => {
+                sequence.containers.iter_mut().for_each(|c| c.template())
+            }
+            DlgeType::Null => {}
+        }
+    }
+
+    fn wav_hashes(&self, hashes: &mut Vec<u32>) {
+        match self {
+            DlgeType::WavFile(wav) => hashes.push(wav.hash()),
+            DlgeType::Random(random) => random.containers.iter().for_each(|c| c.wav_hashes(hashes)),
+            DlgeType::Switch(switch) => switch.containers.iter().for_each(|c| c.wav_hashes(hashes)),
+            DlgeType::Sequence(sequence) => {
+                sequence.containers.iter().for_each(|c| c.wav_hashes(hashes))
+            }
+            DlgeType::Null => {}
+        }
+    }
+
+    // Where the container's first wav is in the reference layout.
+    fn reference_position(&self, positions: &HashMap<u32, usize>) -> usize {
+        let children = match self {
+            DlgeType::WavFile(wav) => return positions.get(&wav.hash()).copied().unwrap_or(usize::MAX),
+            DlgeType::Random(random) => &random.containers,
+            DlgeType::Switch(switch) => &switch.containers,
+            DlgeType::Sequence(sequence) => &sequence.containers,
+            DlgeType::Null => return usize::MAX,
+        };
+
+        children
+            .iter()
+            .map(|child| child.reference_position(positions))
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+
+    // Writes the node and everything under it, returning the node's id.
+    fn to_dot(&self, dot: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        let (label, children) = match self {
+            DlgeType::WavFile(wav) => match &wav.soundtag {
+                Some(soundtag) => (format!("{}\n{}", wav.wav_name, soundtag), &[][..]),
+                None => (wav.wav_name.clone(), &[][..]),
+            },
+            DlgeType::Random(random) => ("Random".to_string(), &random.containers[..]),
+            DlgeType::Switch(switch) => (
+                format!("Switch\n{}\ndefault: {}", switch.switch_key, switch.default),
+                &switch.containers[..],
+            ),
+            DlgeType::Sequence(sequence) => ("Sequence".to_string(), &sequence.containers[..]),
+            DlgeType::Null => ("Null".to_string(), &[][..]),
+        };
+        dot.push_str(&format!("    n{} [label=\"{}\"];\n", id, dot_escape(&label)));
+
+        for (i, child) in children.iter().enumerate() {
+            let child_id = child.to_dot(dot, next_id);
+            let edge = match (self, child) {
+                (DlgeType::Switch(_), DlgeType::WavFile(WavFile { cases, .. }))
+                | (DlgeType::Switch(_), DlgeType::Random(Random { cases, .. })) => cases
+                    .as_ref()
+                    .map(|cases| cases.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")),
+                (DlgeType::Random(_), DlgeType::WavFile(wav)) => wav.weight.as_ref().map(|w| match w {
+                    serde_json::Value::String(w) => w.clone(),
+                    w => w.to_string(),
+                }),
+                (DlgeType::Sequence(_), _) => Some((i + 1).to_string()),
+                _ => None,
+            };
+
+            match edge {
+                Some(edge) => dot.push_str(&format!(
+                    "    n{} -> n{} [label=\"{}\"];\n",
+                    id,
+                    child_id,
+                    dot_escape(&edge)
+                )),
+                None => dot.push_str(&format!("    n{} -> n{};\n", id, child_id)),
+            }
+        }
+
+        id
+    }
+
+    fn flatten(&self, lines: &mut Vec<(String, String, String)>) {
+        match self {
+            DlgeType::WavFile(wav) => {
+                for (language, value) in wav.languages.iter() {
+                    let subtitle = match value {
+                        serde_json::Value::Object(obj) => obj.get("subtitle"),
+                        value => Some(value),
+                    };
+
+                    if let Some(subtitle) = subtitle.and_then(|v| v.as_str()) {
+                        lines.push((wav.wav_name.clone(), language.clone(), subtitle.to_string()));
+                    }
+                }
+            }
+            DlgeType::Random(random) => random.containers.iter().for_each(|c| c.flatten(lines)),
+            DlgeType::Switch(switch) => switch.containers.iter().for_each(|c| c.flatten(lines)),
+            DlgeType::Sequence(sequence) => {
+                sequence.containers.iter().for_each(|c| c.flatten(lines))
+            }
+            DlgeType::Null => {}
+        }
+    }
+}
+
+// Quotes and backslashes are escaped in dot strings, line breaks become \n.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl WavFile {
+    // Wav names are either the hex hash or the name it was hashed from.
+    fn hash(&self) -> u32 {
+        u32_from_hex(&self.wav_name).unwrap_or_else(|| crc32fast::hash(self.wav_name.as_bytes()))
+    }
+}
+
 impl From<WavFile> for DlgeType {
     fn from(v: WavFile) -> Self {
         DlgeType::WavFile(v)
@@ -114,10 +404,19 @@ pub struct DLGE {
     version: Version,
     lang_map: Vec<String>,
     default_locale: String,
-    hex_precision: bool,
+    weight_format: WeightFormat,
     custom_langmap: bool,
-    // This is used for rebuilding.
+    hash_objects: bool,
+    wav_names: bool,
+    orphan_check: bool,
+    strict_cases: bool,
+    equal_weights: bool,
+    string_policy: StringPolicy,
+    collision_policy: CollisionPolicy,
+    // These are used for rebuilding.
     depends: IndexMap<String, String>,
+    depend_flags: Option<Map<String, serde_json::Value>>,
+    wav_positions: Option<HashMap<u32, usize>>,
 }
 
 #[derive(Clone)]
@@ -156,7 +455,7 @@ impl Container {
         for _ in 0..buf.read::<u32>()?.inner() {
             container.metadata.push(Metadata {
                 type_index: buf.read::<u16>()?.inner(),
-                hashes: buf.read_sized_vector::<u32>()?.flatten(),
+                hashes: buf.read_sized_vec::<u32>(None)?,
             })
         }
 
@@ -192,47 +491,98 @@ struct Indices {
     sequence: i32,
 }
 
+// Extracts "foo" from a .../foo.wav or .../foo.animset path.
+fn wav_path_name(wav_hash: &str, ffx_hash: &str) -> Option<String> {
+    let r = Regex::new(r"([^\/]*(?=\.wav))").unwrap();
+    let r_ffx = Regex::new(r"([^\/]*(?=\.animset))").unwrap();
+
+    match r.find(wav_hash).unwrap() {
+        Some(name) => Some(name.as_str().into()),
+        None => r_ffx.find(ffx_hash).unwrap().map(|name| name.as_str().into()),
+    }
+}
+
 fn get_wav_name(wav_hash: &str, ffx_hash: &str, hash: u32) -> String {
     if is_valid_hash(wav_hash) || is_valid_hash(ffx_hash) {
-        return format!("{:08X}", hash);
+        return u32_to_hex(hash);
     }
 
-    let r = Regex::new(r"([^\/]*(?=\.wav))").unwrap();
-    let r_ffx = Regex::new(r"([^\/]*(?=\.animset))").unwrap();
+    wav_path_name(wav_hash, ffx_hash).unwrap_or_else(|| u32_to_hex(hash))
+}
 
-    match r.find(wav_hash).unwrap() {
-        Some(hash) => hash.as_str().into(),
-        None => match r_ffx.find(ffx_hash).unwrap() {
-            Some(hash) => hash.as_str().into(),
-            None => format!("{:08X}", hash),
-        },
+// H2016 DLGEs don't have the tc language that the other formats do.
+fn default_lang_map(version: Version) -> LangResult<Vec<String>> {
+    match version {
+        Version::H2016 => Ok(vec_of_strings![
+            "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp"
+        ]),
+        version => super::default_lang_map(version),
+    }
+}
+
+// How Random weights are written on convert. Weights are a fraction of 0xFFFFFF,
+// Float gives that fraction, which may not round-trip exactly, Hex gives the six
+// digit hex string and Raw gives the stored value as an integer. Rebuild only
+// reads integers as stored values with Raw, see parse_weight.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum WeightFormat {
+    #[default]
+    Float,
+    Hex,
+    Raw,
+}
+
+// Every form is accepted on rebuild. Integers are fractions like any other
+// number, 0 or 1, unless the format is Raw where they're the stored value.
+fn parse_weight(value: &serde_json::Value, format: WeightFormat) -> LangResult<u32> {
+    if let Some(str) = value.as_str() {
+        return u32_from_hex(str).ok_or(LangError::InvalidInput);
+    }
+
+    if let (WeightFormat::Raw, Some(raw)) = (format, value.as_u64()) {
+        return u32::try_from(raw).map_err(|_| LangError::InvalidInput);
+    }
+
+    match value.as_f64() {
+        Some(value) => Ok((value * (0xFFFFFF as f64)).round() as u32),
+        None => Err(LangError::InvalidInput),
+    }
+}
+
+// Languages hold either the subtitle itself or an object with wav, ffx and
+// an optional subtitle.
+fn subtitle_text(value: Option<&serde_json::Value>) -> Option<&str> {
+    match value? {
+        serde_json::Value::String(str) => Some(str),
+        serde_json::Value::Object(obj) => obj.get("subtitle")?.as_str(),
+        _ => None,
     }
 }
 
+// No subtitle and an empty one are both stored as a zero length.
+fn write_subtitle(buf: &mut ByteWriter, subtitle: Option<&str>, policy: StringPolicy) {
+    match subtitle
+        .map(|str| policy.encoded(str))
+        .filter(|str| !str.is_empty())
+    {
+        Some(str) => buf.write_sized_vec(xtea_encrypt(&str)),
+        None => buf.append::<u32>(0),
+    };
+}
+
 impl DLGE {
     pub fn new(
         hashlist: HashList,
         version: Version,
         lang_map: Option<Vec<String>>,
         default_locale: Option<String>,
-        hex_precision: bool,
+        weight_format: WeightFormat,
     ) -> LangResult<Self> {
         let custom_langmap = lang_map.is_some();
         let lang_map = if let Some(map) = lang_map {
             map
         } else {
-            match version {
-                Version::H2016 => vec_of_strings![
-                    "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp"
-                ],
-                Version::H2 => vec_of_strings![
-                    "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp", "tc"
-                ],
-                Version::H3 => {
-                    vec_of_strings!["xx", "en", "fr", "it", "de", "es", "ru", "cn", "tc", "jp"]
-                }
-                _ => return Err(LangError::UnsupportedVersion),
-            }
+            default_lang_map(version)?
         };
 
         let default_locale = default_locale.unwrap_or(String::from("en"));
@@ -242,13 +592,103 @@ impl DLGE {
             version,
             lang_map,
             default_locale,
-            hex_precision,
+            weight_format,
             custom_langmap,
+            hash_objects: false,
+            wav_names: false,
+            orphan_check: false,
+            strict_cases: false,
+            equal_weights: false,
+            string_policy: StringPolicy::default(),
+            collision_policy: CollisionPolicy::default(),
             depends: IndexMap::new(),
+            depend_flags: None,
+            wav_positions: None,
         })
     }
 
+    // Overrides take precedence over the hash list for soundtags and switches,
+    // in both convert and rebuild. They're separate as a hash can be a tag in one
+    // list and something else entirely in the other.
+    pub fn with_overrides(
+        mut self,
+        tags: HashMap<u32, String>,
+        switches: HashMap<u32, String>,
+    ) -> Self {
+        for (hash, name) in tags {
+            self.hashlist.tags.insert(hash, name);
+        }
+        for (hash, name) in switches {
+            self.hashlist.switches.insert(hash, name);
+        }
+        self
+    }
+
+    // Unresolved soundtags and switches are written as {"$hash": "..."} objects
+    // instead of hex strings, so rebuild never mistakes them for names.
+    // LOCR and DITL have no equivalent as their hashes are JSON object keys.
+    pub fn with_hash_objects(mut self, enabled: bool) -> Self {
+        self.hash_objects = enabled;
+        self
+    }
+
+    // Adds a "name" to non-default language {wav, ffx} objects when the paths
+    // give one, same as the wav name. It's only for reading, rebuild ignores it.
+    pub fn with_wav_names(mut self, enabled: bool) -> Self {
+        self.wav_names = enabled;
+        self
+    }
+
+    // Containers that nothing references are dropped on convert, so they'd be lost
+    // on rebuild. With this enabled convert errors instead of dropping them.
+    pub fn with_orphan_check(mut self, enabled: bool) -> Self {
+        self.orphan_check = enabled;
+        self
+    }
+
+    // Switch children must have cases, but an empty list is allowed since the game
+    // accepts it. It's usually a mistake though, so with this enabled rebuild
+    // errors on empty cases too.
+    pub fn with_strict_cases(mut self, enabled: bool) -> Self {
+        self.strict_cases = enabled;
+        self
+    }
+
+    // Random children must have a weight. With this enabled a missing one is given
+    // an equal share, 1 / the number of children, instead of erroring.
+    pub fn with_equal_weights(mut self, enabled: bool) -> Self {
+        self.equal_weights = enabled;
+        self
+    }
+
+    pub fn with_string_policy(mut self, policy: StringPolicy) -> Self {
+        self.string_policy = policy;
+        self
+    }
+
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    // The root container's type as convert would write it, read from the root
+    // marker at the end of the file without converting anything else.
+    pub fn root_type(&self, data: &[u8]) -> LangResult<&'static str> {
+        let marker = data.len().checked_sub(2).ok_or(LangError::InvalidInput)?;
+        let mut buf = ByteReader::new(data, Endianness::Little);
+        buf.seek(marker)?;
+
+        match buf.read::<u16>()?.inner() >> 12 {
+            0x01 => Ok("WavFile"),
+            0x02 => Ok("Random"),
+            0x03 => Ok("Switch"),
+            0x04 => Ok("Sequence"),
+            n => Err(LangError::InvalidContainer(n as u8)),
+        }
+    }
+
     pub fn convert(&self, data: &[u8], meta_json: String) -> LangResult<DlgeJson> {
+        let meta = parse_meta(&meta_json, data)?;
         let mut buf = ByteReader::new(data, Endianness::Little);
 
         let mut j = DlgeJson {
@@ -262,16 +702,33 @@ impl DLGE {
                 None
             },
             root: DlgeType::Null,
+            depend_flags: None,
         };
 
-        let meta: rpkg::ResourceMeta = serde_json::from_str(meta_json.as_str())?;
         j.hash = meta.hash_path.unwrap_or(meta.hash_value);
-        j.ditl = meta.hash_reference_data[buf.read::<u32>()?.inner() as usize]
-            .hash
-            .clone();
-        j.clng = meta.hash_reference_data[buf.read::<u32>()?.inner() as usize]
-            .hash
-            .clone();
+        // These are indices into the depend table, not necessarily 0 and 1.
+        // Dialogue-less DLGEs may reference neither, which we keep as empty strings.
+        let read_reference = |buf: &mut ByteReader| -> LangResult<Option<usize>> {
+            let index = buf.read::<u32>()?.inner();
+            if index == u32::MAX || meta.hash_reference_data.is_empty() {
+                return Ok(None);
+            }
+
+            match meta.hash_reference_data.get(index as usize) {
+                Some(_) => Ok(Some(index as usize)),
+                None => Err(LangError::InvalidInput),
+            }
+        };
+        let ditl = read_reference(&mut buf)?;
+        let clng = read_reference(&mut buf)?;
+
+        let mut depend_flags = Map::new();
+        let reference = |index: Option<usize>| index.map(|i| &meta.hash_reference_data[i]);
+        for index in [ditl, clng] {
+            record_depend_flag(&mut depend_flags, reference(index), "1F");
+        }
+        j.ditl = reference(ditl).map(|d| d.hash.clone()).unwrap_or_default();
+        j.clng = reference(clng).map(|d| d.hash.clone()).unwrap_or_default();
 
         // We setup these maps to store the various types of containers
         // and the latest index for final construction later.
@@ -284,6 +741,13 @@ impl DLGE {
             sequence: 0,
         };
 
+        let depend = |index: u32| {
+            meta.hash_reference_data
+                .get(index as usize)
+                .map(|depend| depend.hash.clone())
+                .ok_or(LangError::InvalidInput)
+        };
+
         // Weirdly, sequences reference by some "global id" for certain types so we store this here.
         let mut globals: IndexMap<u32, usize> = IndexMap::new();
 
@@ -299,21 +763,19 @@ impl DLGE {
                     }
 
                     let mut wav = WavFile {
-                        wav_name: format!("{:08X}", wav_hash),
+                        wav_name: u32_to_hex(wav_hash),
                         cases: None,
                         weight: None,
-                        soundtag: self
-                            .hashlist
-                            .tags
-                            .get_by_left(&tag_hash)
-                            .unwrap_or(&format!("{:08X}", tag_hash))
-                            .clone(),
+                        soundtag: match tag_hash {
+                            0 => None,
+                            _ => Some(lookup_name(&self.hashlist.tags, tag_hash, self.hash_objects)),
+                        },
                         default_wav: None,
                         default_ffx: None,
                         languages: Map::new(),
                     };
 
-                    for language in self.lang_map.as_slice() {
+                    for (lang_index, language) in self.lang_map.iter().enumerate() {
                         if self.version == Version::H2016 {
                             buf.read::<u32>()?.inner();
                         }
@@ -321,38 +783,44 @@ impl DLGE {
                         let wav_index = buf.read::<u32>()?.inner();
                         let ffx_index = buf.read::<u32>()?.inner();
 
+                        for index in [wav_index, ffx_index] {
+                            record_depend_flag(
+                                &mut depend_flags,
+                                meta.hash_reference_data.get(index as usize),
+                                &format!("{:02X}", 0x80 + lang_index),
+                            );
+                        }
+
                         let mut subtitle: serde_json::Value = serde_json::Value::Null;
 
                         if wav_index != u32::MAX && ffx_index != u32::MAX {
-                            if *language == self.default_locale {
-                                wav.default_wav =
-                                    Some(meta.hash_reference_data[wav_index as usize].hash.clone());
-                                wav.default_ffx =
-                                    Some(meta.hash_reference_data[ffx_index as usize].hash.clone());
+                            let wav_depend = depend(wav_index)?;
+                            let ffx_depend = depend(ffx_index)?;
 
-                                wav.wav_name =
-                                    get_wav_name(&wav.default_wav.clone().unwrap(), &wav.default_ffx.clone().unwrap(), wav_hash);
+                            if *language == self.default_locale {
+                                wav.wav_name = get_wav_name(&wav_depend, &ffx_depend, wav_hash);
+                                wav.default_wav = Some(wav_depend);
+                                wav.default_ffx = Some(ffx_depend);
                             } else {
-                                subtitle = json!({
-                                    "wav": meta
-                                        .hash_reference_data
-                                        .get(wav_index as usize)
-                                        .unwrap()
-                                        .clone()
-                                        .hash,
-                                    "ffx": meta
-                                        .hash_reference_data
-                                        .get(ffx_index as usize)
-                                        .unwrap()
-                                        .clone()
-                                        .hash
-                                })
+                                subtitle = json!({"wav": wav_depend, "ffx": ffx_depend});
+
+                                if self.wav_names {
+                                    let name = wav_path_name(
+                                        subtitle["wav"].as_str().unwrap(),
+                                        subtitle["ffx"].as_str().unwrap(),
+                                    );
+                                    if let Some(name) = name {
+                                        subtitle["name"] = name.into();
+                                    }
+                                }
                             }
                         }
 
                         if buf.peek::<u32>()?.inner() != 0 {
-                            let data: serde_json::Value =
-                                xtea_decrypt(buf.read_sized_vector::<u8>()?.flatten())?.into();
+                            let data: serde_json::Value = self
+                                .string_policy
+                                .decoded(xtea_decrypt_raw(buf.read_sized_vec::<u8>(None)?)?)
+                                .into();
 
                             if subtitle.is_null() {
                                 subtitle = data;
@@ -383,17 +851,20 @@ impl DLGE {
                         let index = (metadata.type_index & 0xFFF) as usize;
 
                         if r#type != 0x01 {
-                            return Err(LangError::InvalidReference(r#type as u8));
+                            return Err(LangError::InvalidReference(r#type));
                         }
 
                         if !containers.wav.contains_key(&index) {
-                            return Err(LangError::InvalidReference(index as u8));
+                            return Err(LangError::InvalidReference(index as u16));
                         }
 
-                        containers.wav.get_mut(&index).unwrap().weight = match self.hex_precision {
-                            true => Some(format!("{:06X}", metadata.hashes[0]).into()),
-                            false => Some(((metadata.hashes[0] as f64) / (0xFFFFFF as f64)).into()),
+                        let Some(&weight) = metadata.hashes.first() else {
+                            return Err(LangError::MissingWeight {
+                                wav: containers.wav[&index].wav_name.clone(),
+                            });
                         };
+                        containers.wav.get_mut(&index).unwrap().weight =
+                            Some(self.weight_value(weight));
 
                         random
                             .containers
@@ -409,18 +880,16 @@ impl DLGE {
                 0x03 => {
                     let container = Container::read(&mut buf)?;
                     let mut switch = Switch {
-                        switch_key: self
-                            .hashlist
-                            .switches
-                            .get_by_left(&container.group_hash)
-                            .unwrap_or(&format!("{:08X}", container.group_hash))
-                            .clone(),
-                        default: self
-                            .hashlist
-                            .switches
-                            .get_by_left(&container.default_hash)
-                            .unwrap_or(&format!("{:08X}", container.default_hash))
-                            .clone(),
+                        switch_key: lookup_name(
+                            &self.hashlist.switches,
+                            container.group_hash,
+                            self.hash_objects,
+                        ),
+                        default: lookup_name(
+                            &self.hashlist.switches,
+                            container.default_hash,
+                            self.hash_objects,
+                        ),
                         containers: vec![],
                     };
 
@@ -434,24 +903,18 @@ impl DLGE {
                         let index = (metadata.type_index & 0xFFF) as usize;
 
                         if r#type != 0x01 && r#type != 0x02 {
-                            return Err(LangError::InvalidReference(r#type as u8));
+                            return Err(LangError::InvalidReference(r#type));
                         }
 
-                        let mut cases: Vec<String> = vec![];
+                        let mut cases: Vec<HashName> = vec![];
                         for hash in metadata.hashes {
-                            cases.push(
-                                self.hashlist
-                                    .switches
-                                    .get_by_left(&hash)
-                                    .unwrap_or(&format!("{:08X}", hash))
-                                    .clone(),
-                            )
+                            cases.push(lookup_name(&self.hashlist.switches, hash, self.hash_objects))
                         }
 
                         match r#type {
                             0x01 => {
                                 if !containers.wav.contains_key(&index) {
-                                    return Err(LangError::InvalidReference(index as u8));
+                                    return Err(LangError::InvalidReference(index as u16));
                                 }
 
                                 containers.wav.get_mut(&index).unwrap().cases = cases.into();
@@ -462,7 +925,7 @@ impl DLGE {
                             }
                             0x02 => {
                                 if !containers.random.contains_key(&index) {
-                                    return Err(LangError::InvalidReference(index as u8));
+                                    return Err(LangError::InvalidReference(index as u16));
                                 }
 
                                 containers.random.get_mut(&index).unwrap().cases = cases.into();
@@ -490,18 +953,23 @@ impl DLGE {
                     for metadata in container.metadata {
                         let r#type = metadata.type_index >> 12;
                         if r#type == 0x04 {
-                            return Err(LangError::InvalidReference(r#type as u8));
+                            return Err(LangError::InvalidReference(r#type));
                         }
 
                         let index = match r#type {
-                            0x02 | 0x03 => globals[&((metadata.type_index & 0xFFF) as u32)],
+                            0x02 | 0x03 => {
+                                let global = metadata.type_index & 0xFFF;
+                                *globals
+                                    .get(&(global as u32))
+                                    .ok_or(LangError::InvalidReference(global))?
+                            }
                             _ => (metadata.type_index & 0xFFF) as usize,
                         };
 
                         match r#type {
                             0x01 => {
                                 if !containers.wav.contains_key(&index) {
-                                    return Err(LangError::InvalidReference(index as u8));
+                                    return Err(LangError::InvalidReference(index as u16));
                                 }
 
                                 sequence
@@ -511,7 +979,7 @@ impl DLGE {
                             }
                             0x02 => {
                                 if !containers.random.contains_key(&index) {
-                                    return Err(LangError::InvalidReference(index as u8));
+                                    return Err(LangError::InvalidReference(index as u16));
                                 }
 
                                 sequence
@@ -521,7 +989,7 @@ impl DLGE {
                             }
                             0x03 => {
                                 if !containers.switch.contains_key(&index) {
-                                    return Err(LangError::InvalidReference(index as u8));
+                                    return Err(LangError::InvalidReference(index as u16));
                                 }
 
                                 sequence
@@ -551,48 +1019,154 @@ impl DLGE {
         let root = buf.read::<u16>()?.inner();
         let root_type = root >> 12;
         let root_index = (root & 0xFFF) as u32;
-        let global_index = globals.get(&root_index);
+        let global_index = globals.get(&root_index).copied();
 
+        // The root is moved out like every other referenced container, so anything
+        // left over afterwards isn't reachable from it.
         j.root = match root_type {
             0x01 => containers
                 .wav
-                .get(&(root_index as usize))
-                .unwrap()
-                .clone()
-                .into(),
-            0x02 => containers
-                .random
-                .get(global_index.unwrap())
-                .unwrap()
-                .clone()
-                .into(),
-            0x03 => containers
-                .switch
-                .get(global_index.unwrap())
-                .unwrap()
-                .clone()
-                .into(),
-            0x04 => containers
-                .sequence
-                .get(global_index.unwrap())
-                .unwrap()
-                .clone()
-                .into(),
+                .swap_remove(&(root_index as usize))
+                .map(DlgeType::from),
+            0x02 => global_index
+                .and_then(|i| containers.random.swap_remove(&i))
+                .map(DlgeType::from),
+            0x03 => global_index
+                .and_then(|i| containers.switch.swap_remove(&i))
+                .map(DlgeType::from),
+            0x04 => global_index
+                .and_then(|i| containers.sequence.swap_remove(&i))
+                .map(DlgeType::from),
             n => return Err(LangError::InvalidContainer(n as u8)),
-        };
+        }
+        .ok_or(LangError::InvalidReference(root_index as u16))?;
+
+        let orphans = containers.wav.len()
+            + containers.random.len()
+            + containers.switch.len()
+            + containers.sequence.len();
+        if self.orphan_check && orphans != 0 {
+            return Err(LangError::OrphanedContainers { count: orphans });
+        }
+        if orphans != 0 {
+            warn!("{} unreferenced containers in {} were dropped", orphans, j.hash);
+        }
+
+        if !depend_flags.is_empty() {
+            j.depend_flags = Some(depend_flags);
+        }
 
         Ok(j)
     }
 
+    fn weight_value(&self, weight: u32) -> serde_json::Value {
+        match self.weight_format {
+            WeightFormat::Float => ((weight as f64) / (0xFFFFFF as f64)).into(),
+            WeightFormat::Hex => format!("{:06X}", weight).into(),
+            WeightFormat::Raw => weight.into(),
+        }
+    }
+
+    fn normalize_container(&self, container: &mut DlgeType) -> LangResult<()> {
+        match container {
+            DlgeType::WavFile(wav) => {
+                if let Some(weight) = &wav.weight {
+                    wav.weight = Some(self.weight_value(parse_weight(weight, self.weight_format)?));
+                }
+            }
+            DlgeType::Random(random) => {
+                for child in random.containers.iter_mut() {
+                    self.normalize_container(child)?;
+                }
+            }
+            DlgeType::Switch(switch) => {
+                for child in switch.containers.iter_mut() {
+                    self.normalize_container(child)?;
+                }
+            }
+            DlgeType::Sequence(sequence) => {
+                for child in sequence.containers.iter_mut() {
+                    self.normalize_container(child)?;
+                }
+            }
+            DlgeType::Null => {}
+        }
+
+        Ok(())
+    }
+
+    // Weights are rewritten in the form chosen by `weight_format`.
+    pub fn normalize(&self, json: &str) -> LangResult<String> {
+        let mut json: DlgeJson = parse_json(json)?;
+        self.normalize_container(&mut json.root)?;
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    // Depends are added in the order they're first referenced while writing: DITL, CLNG,
+    // then the wav and ffx of each language, wav file by wav file in file order. An
+    // unmodified file rebuilds with the same depend table as long as the original also
+    // listed its depends in the order they're referenced.
     fn add_depend(&mut self, path: String, flag: String) -> u32 {
+        let path = normalize_depend(&path);
         if self.depends.contains_key(&path) {
             self.depends.get_index_of(&path).unwrap() as u32
         } else {
+            let flag = depend_flag(&self.depend_flags, &path, flag);
             self.depends.insert(path, flag);
             (self.depends.len() - 1) as u32
         }
     }
 
+    // Children are written in JSON order unless rebuild_like found a matching
+    // reference, then they follow where their first wav is in the reference.
+    // The parent still lists them in JSON order.
+    fn emit_order(&self, children: &[DlgeType]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..children.len()).collect();
+        if let Some(positions) = &self.wav_positions {
+            order.sort_by_key(|&i| children[i].reference_position(positions));
+        }
+        order
+    }
+
+    // The wav hashes of a DLGE in the order they're laid out in the file.
+    fn reference_wavs(&self, data: &[u8]) -> LangResult<Vec<u32>> {
+        let mut buf = ByteReader::new(data, Endianness::Little);
+        let mut hashes = Vec::new();
+
+        // Skip the DITL and CLNG indices
+        buf.seek(0x8)?;
+
+        while buf.cursor.len() != 2 {
+            match buf.peek::<u8>()?.inner() {
+                0x01 => {
+                    // Skip the type and soundtag
+                    buf.seek(buf.cursor() + 5)?;
+                    hashes.push(buf.read::<u32>()?.inner());
+
+                    if self.version != Version::H2016 {
+                        buf.read::<u32>()?;
+                    }
+
+                    for _ in &self.lang_map {
+                        if self.version == Version::H2016 {
+                            buf.read::<u32>()?;
+                        }
+
+                        // Skip the wav and ffx indices
+                        buf.read::<u64>()?;
+                        buf.read_sized_vec::<u8>(None)?;
+                    }
+                }
+                0x02..=0x04 => {
+                    Container::read(&mut buf)?;
+                }
+                r#type => return Err(LangError::InvalidContainer(r#type)),
+            }
+        }
+
+        Ok(hashes)
+    }
+
     fn process_container(
         &mut self,
         buf: &mut ByteWriter,
@@ -602,12 +1176,30 @@ impl DLGE {
     ) -> LangResult<()> {
         match container {
             DlgeType::WavFile(wav) => {
+                let invalid = wav.languages.iter().find(|(_, value)| {
+                    !matches!(
+                        value,
+                        serde_json::Value::String(_) | serde_json::Value::Object(_) | serde_json::Value::Null
+                    )
+                });
+                if let Some((language, _)) = invalid {
+                    return Err(LangError::InvalidLanguageValue {
+                        wav: wav.wav_name.clone(),
+                        language: language.clone(),
+                    });
+                }
+
                 buf.append::<u8>(0x01);
-                buf.append::<u32>(*self.hashlist.tags.get_by_right(&wav.soundtag).unwrap());
-                buf.append::<u32>(
-                    u32::from_str_radix(&wav.wav_name, 16)
-                        .unwrap_or(crc32fast::hash(wav.wav_name.as_bytes())),
-                );
+                buf.append::<u32>(match &wav.soundtag {
+                    Some(soundtag) => resolve_hash_name(
+                        &self.hashlist.tags,
+                        soundtag,
+                        self.hash_objects,
+                        self.collision_policy,
+                    )?,
+                    None => 0,
+                });
+                buf.append::<u32>(wav.hash());
 
                 if self.version != Version::H2016 {
                     buf.append::<u32>(0x00);
@@ -632,22 +1224,11 @@ impl DLGE {
                             ),
                         );
 
-                        if wav.languages.contains_key(language) {
-                            match wav.languages.get(language).unwrap().as_str() {
-                                Some(str) => {
-                                    if str.is_empty() {
-                                        buf.append::<u32>(0);
-                                    }
-
-                                    buf.write_sized_vec(xtea_encrypt(str));
-                                }
-                                None => {
-                                    buf.append::<u32>(0);
-                                }
-                            }
-                        } else {
-                            buf.append::<u32>(0);
-                        }
+                        write_subtitle(
+                            buf,
+                            subtitle_text(wav.languages.get(language)),
+                            self.string_policy,
+                        );
                     } else {
                         if !wav.languages.contains_key(language) {
                             buf.append::<u64>(u64::MAX);
@@ -658,35 +1239,38 @@ impl DLGE {
 
                         match wav.languages.get(language).unwrap().as_object() {
                             Some(obj) => {
+                                let (Some(wav_path), Some(ffx_path)) = (
+                                    obj.get("wav").and_then(|v| v.as_str()),
+                                    obj.get("ffx").and_then(|v| v.as_str()),
+                                ) else {
+                                    return Err(LangError::InvalidInput);
+                                };
+
                                 buf.append(self.add_depend(
-                                    obj["wav"].to_string(),
+                                    wav_path.to_string(),
                                     format!("{:02X}", 0x80 + index),
                                 ));
                                 buf.append(self.add_depend(
-                                    obj["ffx"].to_string(),
+                                    ffx_path.to_string(),
                                     format!("{:02X}", 0x80 + index),
                                 ));
 
-                                if obj.contains_key("subtitle") {
-                                    let subtitle = obj["subtitle"].as_str().unwrap();
-                                    buf.write_sized_vec(xtea_encrypt(subtitle));
-                                } else {
-                                    buf.append::<u32>(0);
-                                }
-
+                                write_subtitle(
+                                    buf,
+                                    subtitle_text(wav.languages.get(language)),
+                                    self.string_policy,
+                                );
                                 continue;
                             }
                             None => {
+                                // Subtitle without audio, this is also how the default
+                                // locale is written when it has no defaultWav/defaultFfx.
                                 buf.append::<u64>(u64::MAX);
-
-                                if wav.languages.get(language).unwrap().is_string() {
-                                    let subtitle =
-                                        wav.languages.get(language).unwrap().as_str().unwrap();
-                                    buf.write_sized_vec(xtea_encrypt(subtitle));
-                                } else {
-                                    buf.append::<u32>(0);
-                                }
-
+                                write_subtitle(
+                                    buf,
+                                    subtitle_text(wav.languages.get(language)),
+                                    self.string_policy,
+                                );
                                 continue;
                             }
                         }
@@ -697,15 +1281,18 @@ impl DLGE {
             }
             DlgeType::Random(random) => {
                 let mut container = Container::new(0x02, 0, 0);
+                let mut metadata = vec![None; random.containers.len()];
 
-                for child in random.containers.clone() {
-                    match child {
+                for i in self.emit_order(&random.containers) {
+                    match random.containers[i].clone() {
                         DlgeType::WavFile(wav) => {
-                            if wav.weight.is_none() {
-                                return Err(LangError::InvalidReference(0x01));
-                            }
-
-                            let weight_value = wav.weight.clone().unwrap();
+                            let weight_value = match wav.weight.clone() {
+                                Some(weight) => weight,
+                                None if self.equal_weights => {
+                                    (1.0 / random.containers.len() as f64).into()
+                                }
+                                None => return Err(LangError::MissingWeight { wav: wav.wav_name }),
+                            };
 
                             self.process_container(
                                 buf,
@@ -714,16 +1301,9 @@ impl DLGE {
                                 false,
                             )?;
 
-                            let weight: u32 = match weight_value.as_str() {
-                                Some(str) => u32::from_str_radix(str, 16)?,
-                                None => {
-                                    // Must be double
-                                    let value = weight_value.as_f64().unwrap();
-                                    (value * (0xFFFFFF as f64)).round() as u32
-                                }
-                            };
+                            let weight = parse_weight(&weight_value, self.weight_format)?;
 
-                            container.metadata.push(Metadata {
+                            metadata[i] = Some(Metadata {
                                 type_index: ((0x01 << 12) | (indices.wav & 0xFFF)) as u16,
                                 hashes: vec![weight],
                             });
@@ -734,6 +1314,7 @@ impl DLGE {
                     }
                 }
 
+                container.metadata = metadata.into_iter().flatten().collect();
                 container.write(buf);
                 indices.global += 1;
                 indices.random += 1;
@@ -745,40 +1326,44 @@ impl DLGE {
 
                 let mut container = Container::new(
                     3,
-                    *self
-                        .hashlist
-                        .switches
-                        .get_by_right(&switch.switch_key)
-                        .unwrap_or(
-                            &u32::from_str_radix(&switch.switch_key, 16)
-                                .unwrap_or(crc32fast::hash(switch.switch_key.as_bytes())),
-                        ),
-                    *self
-                        .hashlist
-                        .switches
-                        .get_by_right(&switch.default)
-                        .unwrap_or(
-                            &u32::from_str_radix(&switch.default, 16)
-                                .unwrap_or(crc32fast::hash(switch.default.as_bytes())),
-                        ),
+                    resolve_hash_name(
+                        &self.hashlist.switches,
+                        &switch.switch_key,
+                        self.hash_objects,
+                        self.collision_policy,
+                    )?,
+                    resolve_hash_name(
+                        &self.hashlist.switches,
+                        &switch.default,
+                        self.hash_objects,
+                        self.collision_policy,
+                    )?,
                 );
 
-                for child in switch.containers.clone() {
+                let mut metadata = vec![None; switch.containers.len()];
+
+                for i in self.emit_order(&switch.containers) {
+                    let child = switch.containers[i].clone();
                     let mut cases: Vec<u32> = Vec::new();
 
-                    let source_cases: Vec<String> = match child.clone() {
-                        DlgeType::WavFile(container) => {
-                            if container.cases.is_none() {
-                                return Err(LangError::InvalidReference(0x15));
+                    // Strict mode treats empty cases like missing ones.
+                    let source_cases: Vec<HashName> = match child.clone() {
+                        DlgeType::WavFile(container) => match container.cases {
+                            Some(cases) if !(self.strict_cases && cases.is_empty()) => cases,
+                            _ => {
+                                return Err(LangError::MissingCases {
+                                    container: container.wav_name,
+                                })
                             }
-                            container.cases.unwrap()
-                        }
-                        DlgeType::Random(container) => {
-                            if container.cases.is_none() {
-                                return Err(LangError::InvalidReference(0x15));
+                        },
+                        DlgeType::Random(container) => match container.cases {
+                            Some(cases) if !(self.strict_cases && cases.is_empty()) => cases,
+                            _ => {
+                                return Err(LangError::MissingCases {
+                                    container: format!("Random #{} in {}", i, switch.switch_key),
+                                })
                             }
-                            container.cases.unwrap()
-                        }
+                        },
                         _ => {
                             return Err(LangError::InvalidReference(0x15));
                         }
@@ -787,12 +1372,12 @@ impl DLGE {
                     self.process_container(buf, &mut child.clone(), indices.borrow_mut(), false)?;
 
                     for case in source_cases {
-                        cases.push(
-                            *self.hashlist.switches.get_by_right(&case).unwrap_or(
-                                &u32::from_str_radix(&case, 16)
-                                    .unwrap_or(crc32fast::hash(case.as_bytes())),
-                            ),
-                        );
+                        cases.push(resolve_hash_name(
+                            &self.hashlist.switches,
+                            &case,
+                            self.hash_objects,
+                            self.collision_policy,
+                        )?);
                     }
 
                     let index = match child {
@@ -801,7 +1386,7 @@ impl DLGE {
                         _ => return Err(LangError::InvalidReference(0x15)),
                     };
 
-                    container.metadata.push(Metadata {
+                    metadata[i] = Some(Metadata {
                         type_index: ((i32::from(child) << 12) | (index & 0xFFF)) as u16,
                         hashes: cases,
                     });
@@ -809,6 +1394,7 @@ impl DLGE {
                     indices.switch += 1;
                 }
 
+                container.metadata = metadata.into_iter().flatten().collect();
                 container.write(buf);
                 indices.global += 1;
                 indices.switch += 1;
@@ -819,8 +1405,10 @@ impl DLGE {
                 }
 
                 let mut container = Container::new(4, 0, 0);
+                let mut metadata = vec![None; sequence.containers.len()];
 
-                for child in sequence.containers.clone() {
+                for i in self.emit_order(&sequence.containers) {
+                    let child = sequence.containers[i].clone();
                     self.process_container(buf, &mut child.clone(), indices.borrow_mut(), false)?;
 
                     let index = match child {
@@ -829,7 +1417,7 @@ impl DLGE {
                         _ => return Err(LangError::InvalidReference(0x15)),
                     };
 
-                    container.metadata.push(Metadata {
+                    metadata[i] = Some(Metadata {
                         type_index: ((i32::from(child) << 12) | (index & 0xFFF)) as u16,
                         hashes: vec![],
                     });
@@ -837,11 +1425,13 @@ impl DLGE {
                     indices.sequence += 1;
                 }
 
+                container.metadata = metadata.into_iter().flatten().collect();
                 container.write(buf);
                 indices.global += 1;
                 indices.sequence += 1;
             }
-            _ => {}
+            // Only reachable for a Null root, which rebuild rejects.
+            DlgeType::Null => {}
         }
 
         if is_root {
@@ -858,30 +1448,52 @@ impl DLGE {
     }
 
     pub fn rebuild(&mut self, json: String) -> LangResult<Rebuilt> {
+        self.rebuild_with(json, None)
+    }
+
+    // Rebuilds with the containers laid out like an existing DLGE, so the output
+    // byte diffs cleanly against it. Each container's children are written in the
+    // order their first wav (by hash) appears in the reference. If the reference
+    // can't be read or doesn't have exactly the same wavs, the default JSON
+    // ordering is used.
+    pub fn rebuild_like(&mut self, json: String, reference: &[u8]) -> LangResult<Rebuilt> {
+        self.rebuild_with(json, Some(reference))
+    }
+
+    fn rebuild_with(&mut self, json: String, reference: Option<&[u8]>) -> LangResult<Rebuilt> {
         self.depends.clear();
 
-        let mut json: DlgeJson = serde_json::from_str(&json)?;
+        let mut json: DlgeJson = parse_json(&json)?;
+        self.depend_flags = json.depend_flags.take();
+
+        // A Null root has nothing to write and no valid root marker.
+        // Null children are rejected by their parent container (InvalidReference(0x15)).
+        if matches!(json.root, DlgeType::Null) {
+            return Err(LangError::InvalidInput);
+        }
 
         // The langmap property overrides the struct's language map.
         // This property ensures easy compat with tools like SMF.
-        // We restore this back later.
-        let mut old_langmap: Option<Vec<String>> = None;
-        if json.langmap.is_some() {
-            old_langmap = Some(self.lang_map.clone());
-            self.lang_map = json
-                .langmap
-                .unwrap()
-                .split(',')
-                .map(|s| s.to_string())
-                .collect();
-        };
+        // We restore this back later, whether or not the rebuild succeeds, as the
+        // converter is reused across files in batch rebuilds.
+        let old_langmap = json.langmap.take().map(|langmap| {
+            std::mem::replace(&mut self.lang_map, langmap.split(',').map(|s| s.to_string()).collect())
+        });
 
         let mut buf = ByteWriter::new(Endianness::Little);
 
-        buf.append::<u32>(0x00);
-        self.depends.insert(json.ditl, String::from("1F"));
-        buf.append::<u32>(0x01);
-        self.depends.insert(json.clng, String::from("1F"));
+        // DITL and CLNG become the first two depends. The file stores the
+        // depend index of each, so this is safe even if the original resource had
+        // them elsewhere in its depend table (convert reads whichever index is used).
+        // Empty references are written as u32::MAX and not added as depends.
+        for reference in [json.ditl, json.clng] {
+            if reference.is_empty() {
+                buf.append::<u32>(u32::MAX);
+            } else {
+                let index = self.add_depend(reference, String::from("1F"));
+                buf.append::<u32>(index);
+            }
+        }
 
         // 0 is the "global" index
         let mut indices = Indices {
@@ -892,20 +1504,575 @@ impl DLGE {
             sequence: -1,
         };
 
-        self.process_container(&mut buf, &mut json.root, indices.borrow_mut(), true)?;
+        self.wav_positions = reference.and_then(|reference| {
+            let mut wavs = self.reference_wavs(reference).ok()?;
+            let positions = wavs.iter().enumerate().rev().map(|(i, hash)| (*hash, i)).collect();
+
+            let mut json_wavs = Vec::new();
+            json.root.wav_hashes(&mut json_wavs);
+            json_wavs.sort_unstable();
+            wavs.sort_unstable();
+
+            (wavs == json_wavs).then_some(positions)
+        });
 
-        if old_langmap.is_some() {
-            self.lang_map = old_langmap.unwrap();
+        let result = self.process_container(&mut buf, &mut json.root, indices.borrow_mut(), true);
+
+        if let Some(langmap) = old_langmap {
+            self.lang_map = langmap;
         }
+        result?;
+
+        check_min_size(buf.len(), MIN_SIZE)?;
 
-        Ok(Rebuilt {
-            file: buf.buf(),
-            meta: serde_json::to_string(&ResourceMeta::new(
+        Rebuilt::new(
+            buf.buf(),
+            &ResourceMeta::new(
                 json.hash,
                 buf.len() as u32,
                 "DLGE".into(),
                 self.depends.clone(),
-            ))?,
-        })
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bimap::BiMap;
+    use serde_json::Value;
+
+    fn hashlist() -> HashList {
+        HashList {
+            tags: BiMap::from_iter([(1, "Tag".to_string())]),
+            switches: BiMap::from_iter([(2, "Switch".to_string())]),
+            lines: BiMap::new(),
+            version: 1,
+        }
+    }
+
+    fn dlge() -> DLGE {
+        DLGE::new(hashlist(), Version::H3, None, None, WeightFormat::default()).unwrap()
+    }
+
+    fn json(langmap: Option<&str>, languages: Value) -> String {
+        let mut json = json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "DITL": "",
+            "CLNG": "",
+            "rootContainer": {"type": "WavFile", "wavName": "line", "languages": languages}
+        });
+        if let Some(langmap) = langmap {
+            json["langmap"] = langmap.into();
+        }
+        json.to_string()
+    }
+
+    #[test]
+    fn langmap_does_not_leak_between_rebuilds() {
+        let plain = json(None, json!({"en": "Hello"}));
+        let expected = dlge().rebuild(plain.clone()).unwrap().file;
+
+        let mut dlge = dlge();
+        let bad = json(Some("en,fr"), json!({"en": 5}));
+        assert!(dlge.rebuild(bad).is_err());
+        assert_eq!(dlge.rebuild(plain.clone()).unwrap().file, expected);
+
+        let custom = json(Some("en,fr"), json!({"en": "Hello", "fr": "Bonjour"}));
+        assert!(dlge.rebuild(custom).is_ok());
+        assert_eq!(dlge.rebuild(plain).unwrap().file, expected);
+    }
+
+    // Rebuilds a Random with one wav of the given weight, then converts it back and
+    // returns the weight in Raw form.
+    fn weight_round_trip(rebuild_format: WeightFormat, weight: Value) -> Value {
+        let wav = json!({"type": "WavFile", "wavName": "line", "weight": weight, "languages": {}});
+        let mut json: Value = serde_json::from_str(&json(None, json!({}))).unwrap();
+        json["rootContainer"] = json!({"type": "Random", "containers": [wav]});
+
+        let mut dlge = DLGE::new(hashlist(), Version::H3, None, None, rebuild_format).unwrap();
+        let rebuilt = dlge.rebuild(json.to_string()).unwrap();
+
+        let dlge = DLGE::new(hashlist(), Version::H3, None, None, WeightFormat::Raw).unwrap();
+        let json = dlge.convert(&rebuilt.file, rebuilt.meta).unwrap();
+        let DlgeType::Random(random) = json.root else {
+            panic!("root isn't a Random");
+        };
+        let DlgeType::WavFile(wav) = &random.containers[0] else {
+            panic!("child isn't a WavFile");
+        };
+        wav.weight.clone().unwrap()
+    }
+
+    #[test]
+    fn integer_weights_are_fractions_unless_raw() {
+        assert_eq!(
+            weight_round_trip(WeightFormat::Float, json!(1)),
+            json!(0xFFFFFF)
+        );
+        assert_eq!(
+            weight_round_trip(WeightFormat::Float, json!(0.5)),
+            json!(0x800000)
+        );
+        assert_eq!(
+            weight_round_trip(WeightFormat::Hex, json!("123457")),
+            json!(0x123457)
+        );
+
+        assert_eq!(weight_round_trip(WeightFormat::Raw, json!(1)), json!(1));
+        assert_eq!(
+            weight_round_trip(WeightFormat::Raw, json!(0x123457)),
+            json!(0x123457)
+        );
+        assert_eq!(
+            weight_round_trip(WeightFormat::Raw, json!(1.0)),
+            json!(0xFFFFFF)
+        );
+    }
+
+    // A Random of two wavs, one with audio in two languages and one with only the
+    // default locale. Every depend is a hash so the order is easy to check.
+    fn random_json() -> Value {
+        let mut json: Value = serde_json::from_str(&json(None, json!({}))).unwrap();
+        json["DITL"] = "00A0000000000000".into();
+        json["CLNG"] = "00B0000000000000".into();
+        json["rootContainer"] = json!({
+            "type": "Random",
+            "containers": [
+                {
+                    "type": "WavFile",
+                    "wavName": "first",
+                    "weight": 0.5,
+                    "defaultWav": "00C0000000000000",
+                    "defaultFfx": "00D0000000000000",
+                    "languages": {
+                        "en": "Hello",
+                        "fr": {"wav": "00E0000000000000", "ffx": "00F0000000000000", "subtitle": "Bonjour"}
+                    }
+                },
+                {
+                    "type": "WavFile",
+                    "wavName": "second",
+                    "weight": 0.5,
+                    "defaultWav": "00C1000000000000",
+                    "defaultFfx": "00D1000000000000",
+                    "languages": {"en": "Goodbye"}
+                }
+            ]
+        });
+        json
+    }
+
+    fn depends(meta: &str) -> Vec<(String, String)> {
+        ResourceMeta::from_json(meta)
+            .unwrap()
+            .hash_reference_data
+            .into_iter()
+            .map(|depend| (depend.hash, depend.flag))
+            .collect()
+    }
+
+    #[test]
+    fn unmodified_round_trip_keeps_the_depend_order() {
+        let mut dlge = dlge();
+        let rebuilt = dlge.rebuild(random_json().to_string()).unwrap();
+
+        let hashes: Vec<_> = depends(&rebuilt.meta)
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect();
+        assert_eq!(
+            hashes,
+            [
+                "00A0000000000000",
+                "00B0000000000000",
+                "00C0000000000000",
+                "00D0000000000000",
+                "00E0000000000000",
+                "00F0000000000000",
+                "00C1000000000000",
+                "00D1000000000000",
+            ]
+        );
+
+        let converted = dlge.convert(&rebuilt.file, rebuilt.meta.clone()).unwrap();
+        let again = dlge
+            .rebuild(serde_json::to_string(&converted).unwrap())
+            .unwrap();
+        assert_eq!(depends(&again.meta), depends(&rebuilt.meta));
+        assert_eq!(again.file, rebuilt.file);
+    }
+
+    #[test]
+    fn null_containers_are_rejected() {
+        let mut json = random_json();
+        json["rootContainer"] = json!({"type": "Null"});
+        assert!(matches!(
+            dlge().rebuild(json.to_string()),
+            Err(LangError::InvalidInput)
+        ));
+
+        let mut json = random_json();
+        json["rootContainer"]["containers"][1] = json!({"type": "Null"});
+        assert!(matches!(
+            dlge().rebuild(json.to_string()),
+            Err(LangError::InvalidReference(0x15))
+        ));
+    }
+
+    #[test]
+    fn dependency_less_round_trip() {
+        let mut dlge = dlge();
+        let rebuilt = dlge.rebuild(json(None, json!({"en": "Hello"}))).unwrap();
+        assert!(depends(&rebuilt.meta).is_empty());
+
+        let converted = dlge.convert(&rebuilt.file, rebuilt.meta).unwrap();
+        assert_eq!(converted.ditl, "");
+        assert_eq!(converted.clng, "");
+    }
+
+    #[test]
+    fn default_locale_subtitle_without_audio() {
+        let languages = json!({
+            "en": "Hello",
+            "fr": {"wav": "00E0000000000000", "ffx": "00F0000000000000", "subtitle": "Bonjour"}
+        });
+
+        let mut dlge = dlge();
+        let rebuilt = dlge.rebuild(json(None, languages.clone())).unwrap();
+        let converted = dlge.convert(&rebuilt.file, rebuilt.meta).unwrap();
+
+        let DlgeType::WavFile(wav) = converted.root else {
+            panic!("root isn't a WavFile");
+        };
+        assert_eq!(wav.default_wav, None);
+        assert_eq!(wav.default_ffx, None);
+        assert_eq!(wav.languages["en"], "Hello");
+        assert_eq!(wav.languages["fr"], languages["fr"]);
+    }
+
+    #[test]
+    fn depend_flags_round_trip() {
+        let mut json = random_json();
+        json["dependFlags"] = json!({"00A0000000000000": "01", "00E0000000000000": "9F"});
+
+        let mut dlge = dlge();
+        let rebuilt = dlge.rebuild(json.to_string()).unwrap();
+        let flags: Vec<_> = depends(&rebuilt.meta)
+            .into_iter()
+            .map(|(_, flag)| flag)
+            .collect();
+        assert_eq!(flags, ["01", "1F", "81", "81", "9F", "82", "81", "81"]);
+
+        let converted = dlge.convert(&rebuilt.file, rebuilt.meta.clone()).unwrap();
+        assert_eq!(
+            converted.depend_flags,
+            json["dependFlags"].as_object().cloned()
+        );
+
+        let again = dlge
+            .rebuild(serde_json::to_string(&converted).unwrap())
+            .unwrap();
+        assert_eq!(depends(&again.meta), depends(&rebuilt.meta));
+    }
+
+    #[test]
+    fn tagless_wavs() {
+        let mut dlge = dlge();
+        let rebuilt = dlge.rebuild(json(None, json!({"en": "Hello"}))).unwrap();
+
+        for tag in ["", "0"] {
+            let mut json: Value =
+                serde_json::from_str(&json(None, json!({"en": "Hello"}))).unwrap();
+            json["rootContainer"]["soundtag"] = tag.into();
+            assert_eq!(dlge.rebuild(json.to_string()).unwrap().file, rebuilt.file);
+        }
+
+        let converted = dlge.convert(&rebuilt.file, rebuilt.meta).unwrap();
+        let DlgeType::WavFile(wav) = converted.root else {
+            panic!("root isn't a WavFile");
+        };
+        assert!(wav.soundtag.is_none());
+    }
+
+    #[test]
+    fn bom_prefixed_json() {
+        let json = random_json().to_string();
+        let expected = dlge().rebuild(json.clone()).unwrap();
+        let rebuilt = dlge().rebuild(format!("\u{FEFF}{}", json)).unwrap();
+
+        assert_eq!(rebuilt.file, expected.file);
+        assert_eq!(rebuilt.meta, expected.meta);
+    }
+
+    #[test]
+    fn orphaned_containers() {
+        let rebuilt = dlge().rebuild(random_json().to_string()).unwrap();
+
+        // A wav that nothing references, taken from a file where it's the root.
+        // That file is the DITL and CLNG indices, the wav, then the root marker.
+        let single = dlge()
+            .rebuild(json(None, json!({"en": "Extra"})))
+            .unwrap()
+            .file;
+        let mut orphaned = rebuilt.file.clone();
+        orphaned.splice(
+            orphaned.len() - 2..orphaned.len() - 2,
+            single[8..single.len() - 2].to_vec(),
+        );
+
+        let mut meta: Value = serde_json::from_str(&rebuilt.meta).unwrap();
+        meta["hash_size_final"] = orphaned.len().into();
+        let meta = meta.to_string();
+
+        assert!(matches!(
+            dlge()
+                .with_orphan_check(true)
+                .convert(&orphaned, meta.clone()),
+            Err(LangError::OrphanedContainers { count: 1 })
+        ));
+        assert!(dlge().convert(&orphaned, meta).is_ok());
+
+        // Indices are 12 bits and reported in full.
+        let mut file = rebuilt.file.clone();
+        let len = file.len();
+        file[len - 2..].copy_from_slice(&0x1123_u16.to_le_bytes());
+        assert!(matches!(
+            dlge().convert(&file, rebuilt.meta),
+            Err(LangError::InvalidReference(0x123))
+        ));
+    }
+
+    #[test]
+    fn overrides_only_touch_their_own_list() {
+        let dlge = dlge().with_overrides(
+            HashMap::from([(1, "Override".to_string())]),
+            HashMap::from([(3, "Other".to_string())]),
+        );
+
+        assert_eq!(dlge.hashlist.tags.get_by_left(&1).unwrap(), "Override");
+        assert_eq!(dlge.hashlist.tags.get_by_left(&3), None);
+        assert_eq!(dlge.hashlist.switches.get_by_left(&1), None);
+        assert_eq!(dlge.hashlist.switches.get_by_left(&2).unwrap(), "Switch");
+        assert_eq!(dlge.hashlist.switches.get_by_left(&3).unwrap(), "Other");
+    }
+
+    #[test]
+    fn csv_escapes_tricky_subtitles() {
+        let tricky = "Hello, \"Agent 47\"\nGoodbye";
+        let json: DlgeJson =
+            serde_json::from_str(&json(None, json!({"en": tricky, "fr": "Bonjour"}))).unwrap();
+
+        let records = csv::parse(&json.to_csv()).unwrap();
+        let records: Vec<_> = records.into_iter().map(|(_, record)| record).collect();
+        assert_eq!(
+            records,
+            vec![
+                vec!["wav_name", "language", "subtitle"],
+                vec!["line", "en", tricky],
+                vec!["line", "fr", "Bonjour"],
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_cases_are_only_rejected_when_strict() {
+        let switch = |cases: Value| {
+            let mut json: Value = serde_json::from_str(&json(None, json!({}))).unwrap();
+            let mut child = json!({"type": "WavFile", "wavName": "child", "languages": {}});
+            if !cases.is_null() {
+                child["cases"] = cases;
+            }
+            json["rootContainer"] = json!({
+                "type": "Switch",
+                "switchKey": "Switch",
+                "default": "Case",
+                "containers": [child]
+            });
+            json.to_string()
+        };
+
+        let rebuilt = dlge().rebuild(switch(json!([]))).unwrap();
+        let converted = dlge().convert(&rebuilt.file, rebuilt.meta).unwrap();
+        let DlgeType::Switch(root) = converted.root else {
+            panic!("expected a switch root");
+        };
+        let DlgeType::WavFile(child) = &root.containers[0] else {
+            panic!("expected a wav child");
+        };
+        assert_eq!(child.cases.as_deref().map(<[_]>::len), Some(0));
+
+        assert!(matches!(
+            dlge().with_strict_cases(true).rebuild(switch(json!([]))),
+            Err(LangError::MissingCases { container }) if container == "child"
+        ));
+        for strict in [false, true] {
+            assert!(matches!(
+                dlge().with_strict_cases(strict).rebuild(switch(Value::Null)),
+                Err(LangError::MissingCases { container }) if container == "child"
+            ));
+        }
+        assert!(dlge()
+            .with_strict_cases(true)
+            .rebuild(switch(json!(["Case"])))
+            .is_ok());
+    }
+
+    #[test]
+    fn omitted_weights_get_an_equal_share() {
+        let explicit = dlge().rebuild(random_json().to_string()).unwrap();
+
+        let mut json = random_json();
+        for child in json["rootContainer"]["containers"].as_array_mut().unwrap() {
+            child.as_object_mut().unwrap().remove("weight");
+        }
+        let json = json.to_string();
+
+        assert!(matches!(
+            dlge().rebuild(json.clone()),
+            Err(LangError::MissingWeight { wav }) if wav == "first"
+        ));
+        let equal = dlge().with_equal_weights(true).rebuild(json).unwrap();
+        assert_eq!(equal.file, explicit.file);
+    }
+
+    #[test]
+    fn only_single_subtitles_are_accepted() {
+        for value in [json!(["Hello", "Hi"]), json!(5), json!(true)] {
+            let json = json(None, json!({"xx": "", "en": value}));
+            assert!(matches!(
+                dlge().rebuild(json),
+                Err(LangError::InvalidLanguageValue { wav, language })
+                    if wav == "line" && language == "en"
+            ));
+        }
+
+        let json = json(None, json!({"en": "Hello", "fr": null}));
+        assert!(dlge().rebuild(json).is_ok());
+    }
+
+    #[test]
+    fn dot_labels_nodes_and_edges() {
+        let mut json: Value = serde_json::from_str(&json(None, json!({}))).unwrap();
+        json["rootContainer"] = json!({
+            "type": "Sequence",
+            "containers": [
+                {
+                    "type": "Switch",
+                    "switchKey": "Switch",
+                    "default": "Case",
+                    "containers": [{
+                        "type": "Random",
+                        "cases": ["Case", {"$hash": "00000003"}],
+                        "containers": [
+                            {
+                                "type": "WavFile",
+                                "wavName": "first",
+                                "weight": 0.25,
+                                "soundtag": "Tag",
+                                "languages": {}
+                            },
+                            {
+                                "type": "WavFile",
+                                "wavName": "say \"second\"",
+                                "weight": "0x40",
+                                "languages": {}
+                            }
+                        ]
+                    }]
+                },
+                {"type": "WavFile", "wavName": "last", "languages": {}}
+            ]
+        });
+        let json: DlgeJson = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            json.to_dot(),
+            concat!(
+                "digraph DLGE {\n",
+                "    node [shape=box];\n",
+                "    n0 [label=\"Sequence\"];\n",
+                "    n1 [label=\"Switch\\nSwitch\\ndefault: Case\"];\n",
+                "    n2 [label=\"Random\"];\n",
+                "    n3 [label=\"first\\nTag\"];\n",
+                "    n2 -> n3 [label=\"0.25\"];\n",
+                "    n4 [label=\"say \\\"second\\\"\"];\n",
+                "    n2 -> n4 [label=\"0x40\"];\n",
+                "    n1 -> n2 [label=\"Case, 00000003\"];\n",
+                "    n0 -> n1 [label=\"1\"];\n",
+                "    n5 [label=\"last\"];\n",
+                "    n0 -> n5 [label=\"2\"];\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_containers_are_an_error() {
+        // A random child without its weight, the random is last before the root.
+        let rebuilt = dlge().rebuild(random_json().to_string()).unwrap();
+        let mut file = rebuilt.file.clone();
+        let metadata = file.len() - 2 - 2 * 10;
+        file[metadata + 2..metadata + 6].fill(0);
+        file.drain(metadata + 6..metadata + 10);
+        let mut meta: Value = serde_json::from_str(&rebuilt.meta).unwrap();
+        meta["hash_size_final"] = file.len().into();
+        assert!(matches!(
+            dlge().convert(&file, meta.to_string()),
+            Err(LangError::MissingWeight { .. })
+        ));
+
+        // A sequence referencing a random that was never read.
+        let mut json = random_json();
+        json["rootContainer"] = json!({"type": "Sequence", "containers": [json["rootContainer"]]});
+        let rebuilt = dlge().rebuild(json.to_string()).unwrap();
+        let mut file = rebuilt.file.clone();
+        let len = file.len();
+        file[len - 8..len - 6].copy_from_slice(&0x2005_u16.to_le_bytes());
+        assert!(matches!(
+            dlge().convert(&file, rebuilt.meta),
+            Err(LangError::InvalidReference(5))
+        ));
+
+        // Wav and ffx indices past the end of the depends.
+        let rebuilt = dlge().rebuild(random_json().to_string()).unwrap();
+        let mut meta: Value = serde_json::from_str(&rebuilt.meta).unwrap();
+        meta["hash_reference_data"]
+            .as_array_mut()
+            .unwrap()
+            .truncate(2);
+        assert!(matches!(
+            dlge().convert(&rebuilt.file, meta.to_string()),
+            Err(LangError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn root_type_matches_the_converted_json() {
+        let wav = json(None, json!({"en": "Hello"}));
+        let mut sequence = random_json();
+        sequence["rootContainer"] =
+            json!({"type": "Sequence", "containers": [sequence["rootContainer"]]});
+
+        for json in [wav, random_json().to_string(), sequence.to_string()] {
+            let rebuilt = dlge().rebuild(json).unwrap();
+            let converted = dlge().convert(&rebuilt.file, rebuilt.meta).unwrap();
+
+            assert_eq!(
+                dlge().root_type(&rebuilt.file).unwrap(),
+                converted.root_type()
+            );
+        }
+
+        assert!(matches!(
+            dlge().root_type(&[0x01]),
+            Err(LangError::InvalidInput)
+        ));
+        assert!(matches!(
+            dlge().root_type(&[0x00, 0x50]),
+            Err(LangError::InvalidContainer(5))
+        ));
     }
 }
@@ -1,9 +1,14 @@
-use std::error::Error;
+use std::{error::Error, fmt};
 use strum_macros::Display;
 
 use bimap::BiMap;
+use log::debug;
+use serde::{Deserialize, Serialize};
 use bitchomp::{ByteReader, ByteReaderError, Endianness};
 
+use super::{LangError, LangResult};
+use crate::util::hex::{u32_from_hex, u32_to_hex};
+
 #[derive(Clone)]
 pub struct HashList {
     pub tags: BiMap<u32, String>,
@@ -17,7 +22,10 @@ pub enum HashListError {
     InvalidFile,
     InvalidChecksum,
     DidNotReachEOF,
+    UnsupportedVersion(u32),
     ReaderError(ByteReaderError),
+    #[strum(to_string = "MergeConflict({hash:08X})")]
+    MergeConflict { hash: u32 },
 }
 
 impl From<ByteReaderError> for HashListError {
@@ -28,6 +36,120 @@ impl From<ByteReaderError> for HashListError {
 
 impl Error for HashListError {}
 
+// What to do when a name missing from the hash list falls back to a crc32
+// that already belongs to a different known name.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum CollisionPolicy {
+    #[default]
+    Error,
+    Allow,
+}
+
+// How merge handles a hash or name that both lists have, paired differently.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum MergePolicy {
+    #[default]
+    KeepExisting,
+    Overwrite,
+    Error,
+}
+
+// The first entry of from that's paired differently in into.
+fn merge_conflict(into: &BiMap<u32, String>, from: &BiMap<u32, String>) -> Option<u32> {
+    from.iter()
+        .find(|(hash, name)| {
+            into.get_by_left(hash).is_some_and(|existing| existing != *name)
+                || into.get_by_right(*name).is_some_and(|existing| existing != *hash)
+        })
+        .map(|(hash, _)| *hash)
+}
+
+// A name from the hash list or, when hash objects are enabled, a hash that
+// couldn't be resolved. The object form tells rebuild to use the hash as-is.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum HashName {
+    Hash {
+        #[serde(rename = "$hash")]
+        hash: String,
+    },
+    Name(String),
+}
+
+impl fmt::Display for HashName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashName::Hash { hash } => write!(f, "{}", hash),
+            HashName::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+// Looks up the name of a hash. Unknown hashes become hex strings, or hash
+// objects if hash_objects is set.
+pub(crate) fn lookup_name(map: &BiMap<u32, String>, hash: u32, hash_objects: bool) -> HashName {
+    if !map.contains_left(&hash) {
+        debug!("No name for hash {}", u32_to_hex(hash));
+    }
+
+    match map.get_by_left(&hash) {
+        Some(name) => HashName::Name(name.clone()),
+        None if hash_objects => HashName::Hash {
+            hash: u32_to_hex(hash),
+        },
+        None => HashName::Name(u32_to_hex(hash)),
+    }
+}
+
+// The reverse of lookup_name. With hash objects enabled, plain strings are
+// always names, so a name that looks like hex is hashed rather than parsed.
+pub(crate) fn resolve_hash_name(
+    map: &BiMap<u32, String>,
+    name: &HashName,
+    hash_objects: bool,
+    policy: CollisionPolicy,
+) -> LangResult<u32> {
+    match name {
+        HashName::Hash { hash } => u32_from_hex(hash).ok_or(LangError::InvalidInput),
+        HashName::Name(name) if hash_objects => match map.get_by_right(name) {
+            Some(hash) => Ok(*hash),
+            None => crc32_checked(map, name, policy),
+        },
+        HashName::Name(name) => resolve_hash(map, name, policy),
+    }
+}
+
+// Resolves a name to its hash. Known names come from the map, hex strings are
+// taken as the hash itself and anything else is hashed with crc32.
+pub(crate) fn resolve_hash(
+    map: &BiMap<u32, String>,
+    name: &str,
+    policy: CollisionPolicy,
+) -> LangResult<u32> {
+    if let Some(hash) = map.get_by_right(name) {
+        return Ok(*hash);
+    }
+
+    if let Some(hash) = u32_from_hex(name) {
+        return Ok(hash);
+    }
+
+    crc32_checked(map, name, policy)
+}
+
+fn crc32_checked(map: &BiMap<u32, String>, name: &str, policy: CollisionPolicy) -> LangResult<u32> {
+    let hash = crc32fast::hash(name.as_bytes());
+    debug!("{} isn't in the hash list, using its crc32 {}", name, u32_to_hex(hash));
+    if let (CollisionPolicy::Error, Some(existing)) = (policy, map.get_by_left(&hash)) {
+        return Err(LangError::HashCollision {
+            name: name.to_string(),
+            existing: existing.clone(),
+        });
+    }
+
+    Ok(hash)
+}
+
 impl HashList {
     pub fn load(data: &[u8]) -> Result<Self, HashListError> {
         let mut buf = ByteReader::new(data, Endianness::Little);
@@ -44,7 +166,13 @@ impl HashList {
         }
 
         // Version
+        // The version is the hash list's revision rather than a layout version,
+        // every revision so far shares the same layout. u32::MAX is what clear()
+        // uses for "no hash list" so a file can't claim it.
         hashlist.version = buf.read::<u32>()?.inner();
+        if hashlist.version == u32::MAX {
+            return Err(HashListError::UnsupportedVersion(hashlist.version));
+        }
 
         // Checksum
         let checksum = buf.read::<u32>()?.inner();
@@ -76,6 +204,57 @@ impl HashList {
         Ok(hashlist)
     }
 
+    // The hash list built into the binary, None if it fails to load or there was
+    // no list to embed when building.
+    #[cfg(feature = "embedded-hashlist")]
+    pub fn embedded() -> Option<Self> {
+        #[cfg(embedded_hashlist)]
+        {
+            static DATA: &[u8] = include_bytes!(env!("TONYTOOLS_HASH_LIST_PATH"));
+            Self::load(DATA).ok()
+        }
+        #[cfg(not(embedded_hashlist))]
+        None
+    }
+
+    // Adds another list's tags, switches and lines, for layering supplements over a
+    // base list. With MergePolicy::Error nothing is changed if there's a conflict.
+    // The version becomes the newer of the two.
+    pub fn merge(&mut self, other: &HashList, policy: MergePolicy) -> Result<(), HashListError> {
+        let maps = [
+            (&mut self.tags, &other.tags),
+            (&mut self.switches, &other.switches),
+            (&mut self.lines, &other.lines),
+        ];
+
+        if policy == MergePolicy::Error {
+            if let Some(hash) = maps.iter().find_map(|(into, from)| merge_conflict(into, from)) {
+                return Err(HashListError::MergeConflict { hash });
+            }
+        }
+
+        for (into, from) in maps {
+            for (hash, name) in from {
+                if policy == MergePolicy::KeepExisting
+                    && (into.contains_left(hash) || into.contains_right(name))
+                {
+                    continue;
+                }
+
+                // Overwriting drops whatever either side was paired with before.
+                into.insert(*hash, name.clone());
+            }
+        }
+
+        // u32::MAX is an empty or cleared list, not a newer revision.
+        self.version = match (self.version, other.version) {
+            (u32::MAX, version) | (version, u32::MAX) => version,
+            (a, b) => a.max(b),
+        };
+
+        Ok(())
+    }
+
     pub fn clear(&mut self) {
         self.tags.clear();
         self.switches.clear();
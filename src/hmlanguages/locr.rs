@@ -1,29 +1,206 @@
-use super::Rebuilt;
-use super::{hashlist::HashList, LangError, LangResult};
-use crate::util::cipher::{symmetric_decrypt, symmetric_encrypt, xtea_decrypt, xtea_encrypt};
-use crate::util::rpkg::{self, ResourceMeta};
-use crate::util::vec_of_strings;
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::{check_min_size, parse_json, parse_meta, LineDiff, Rebuilt, StringPolicy, ToWriter};
+use super::{
+    default_lang_map,
+    hashlist::{resolve_hash, CollisionPolicy, HashList},
+    normalize, LangError, LangResult,
+};
+use crate::util::cipher::{symmetric_decrypt, symmetric_encrypt, xtea_decrypt_raw, xtea_encrypt};
+use crate::util::csv;
+use crate::util::hex::u32_to_hex;
+use crate::util::reader::ByteReaderExt;
+use crate::util::rpkg::ResourceMeta;
 use crate::Version;
 use bitchomp::{ByteReader, ByteWriter, Endianness, ChompFlatten};
 use indexmap::IndexMap;
+use log::warn;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json::Map;
+use serde_json::{json, Map, Value};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LocrJson {
     #[serde(rename = "$schema")]
     schema: String,
     hash: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     symmetric: Option<bool>,
+    // A language with lines, an empty object or null. Both of the last two are
+    // written as an empty language, which convert gives back as an empty object.
     languages: Map<String, serde_json::Value>,
 }
 
+impl ToWriter for LocrJson {}
+
+impl LocrJson {
+    // Returns every line as (hash, language, text).
+    pub fn flatten(&self) -> Vec<(String, String, String)> {
+        let mut lines = Vec::new();
+
+        for (language, strings) in self.languages.iter() {
+            match strings {
+                Value::Object(strings) => {
+                    for (hash, str) in strings {
+                        if let Some(str) = str.as_str() {
+                            lines.push((hash.clone(), language.clone(), str.to_string()));
+                        }
+                    }
+                }
+                Value::Array(strings) => {
+                    for line in strings {
+                        if let Ok((hash, str)) = numeric_line(line) {
+                            lines.push((u32_to_hex(hash), language.clone(), str.to_string()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        lines
+    }
+
+    // Lines added, removed or changed in other, by hash and language.
+    pub fn diff(&self, other: &LocrJson) -> LineDiff {
+        LineDiff::new(self.flatten(), other.flatten())
+    }
+
+    // A copy with every line emptied, for starting a new translation.
+    pub fn template(&self) -> LocrJson {
+        let mut json = self.clone();
+        for strings in json.languages.values_mut() {
+            match strings {
+                Value::Object(strings) => strings.values_mut().for_each(|str| *str = "".into()),
+                // Anything that isn't a line object is left as is for rebuild to reject.
+                Value::Array(strings) => strings.iter_mut().for_each(|line| {
+                    if let Value::Object(line) = line {
+                        line.insert("text".into(), "".into());
+                    }
+                }),
+                _ => {}
+            }
+        }
+        json
+    }
+
+    // Writes every line as a hash,language,text CSV for spreadsheet editing.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        csv::write_row(&mut csv, &["hash", "language", "text"]);
+
+        for (hash, language, text) in self.flatten() {
+            csv::write_row(&mut csv, &[&hash, &language, &text]);
+        }
+
+        csv
+    }
+
+    // Applies the lines from a CSV written by to_csv, adding any that are new.
+    // The header row is optional, every language must already be in the JSON and
+    // keyed by hash rather than numeric. Nothing is changed if any row is invalid.
+    pub fn merge_csv(&mut self, csv: &str) -> LangResult<()> {
+        let mut edits = Vec::new();
+        for (line, record) in csv::parse(csv)? {
+            let [hash, language, text] = &record[..] else {
+                return Err(LangError::InvalidCsv { line });
+            };
+
+            if line == 1 && hash == "hash" && language == "language" && text == "text" {
+                continue;
+            }
+
+            if !self.languages.get(language).is_some_and(|v| v.is_object()) {
+                return Err(LangError::InvalidLanguageMap);
+            }
+
+            edits.push(record);
+        }
+
+        for edit in edits {
+            if let [hash, language, text] = &edit[..] {
+                self.languages[language][hash] = text.clone().into();
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the number of lines in each language, including empty languages.
+    pub fn line_counts(&self) -> Vec<(String, usize)> {
+        self.languages
+            .iter()
+            .map(|(language, strings)| {
+                let count = match strings {
+                    Value::Object(strings) => strings.len(),
+                    Value::Array(strings) => strings.len(),
+                    _ => 0,
+                };
+                (language.clone(), count)
+            })
+            .collect()
+    }
+
+    // Moves the languages from one game's language map to another's. Languages the
+    // target game doesn't have are dropped with a warning, missing ones are added
+    // empty.
+    pub fn remap(&self, from: Version, to: Version) -> LangResult<LocrJson> {
+        let from_map = default_lang_map(from)?;
+        let to_map = default_lang_map(to)?;
+
+        if self.languages.keys().any(|lang| !from_map.contains(lang)) {
+            return Err(LangError::InvalidLanguageMap);
+        }
+
+        let dropped: Vec<_> = self
+            .languages
+            .keys()
+            .filter(|lang| !to_map.contains(lang))
+            .cloned()
+            .collect();
+        if !dropped.is_empty() {
+            warn!(
+                "Languages {} aren't in the target game and were dropped",
+                dropped.join(", ")
+            );
+        }
+
+        let mut languages = Map::new();
+        for lang in to_map {
+            let strings = self
+                .languages
+                .get(&lang)
+                .cloned()
+                .unwrap_or(Map::new().into());
+            languages.insert(lang, strings);
+        }
+
+        Ok(LocrJson {
+            schema: self.schema.clone(),
+            hash: self.hash.clone(),
+            symmetric: self.symmetric.filter(|_| to == Version::H2016),
+            languages,
+        })
+    }
+}
+
+// A {"hash": 305419896, "text": "..."} line from a numeric language.
+fn numeric_line(line: &Value) -> LangResult<(u32, &str)> {
+    let hash = line["hash"].as_u64().and_then(|hash| u32::try_from(hash).ok());
+    let text = line["text"].as_str();
+
+    hash.zip(text).ok_or(LangError::InvalidInput)
+}
+
 pub struct LOCR {
     hashlist: HashList,
     version: Version,
     lang_map: Vec<String>,
     symmetric: bool,
+    string_policy: StringPolicy,
+    collision_policy: CollisionPolicy,
+    numeric_hashes: bool,
 }
 
 impl LOCR {
@@ -36,15 +213,7 @@ impl LOCR {
         let lang_map = if let Some(map) = lang_map {
             map
         } else {
-            match version {
-                Version::H2016 | Version::H2 => vec_of_strings![
-                    "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp", "tc"
-                ],
-                Version::H3 => {
-                    vec_of_strings!["xx", "en", "fr", "it", "de", "es", "ru", "cn", "tc", "jp"]
-                }
-                _ => return Err(LangError::UnsupportedVersion),
-            }
+            default_lang_map(version)?
         };
 
         Ok(LOCR {
@@ -52,18 +221,43 @@ impl LOCR {
             version,
             lang_map,
             symmetric,
+            string_policy: StringPolicy::default(),
+            collision_policy: CollisionPolicy::default(),
+            numeric_hashes: false,
         })
     }
 
+    // Overrides take precedence over the hash list in both convert and rebuild.
+    pub fn with_overrides(mut self, overrides: HashMap<u32, String>) -> Self {
+        for (hash, name) in overrides {
+            self.hashlist.lines.insert(hash, name);
+        }
+        self
+    }
+
+    pub fn with_string_policy(mut self, policy: StringPolicy) -> Self {
+        self.string_policy = policy;
+        self
+    }
+
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    // Converts each language to an array of {"hash": 305419896, "text": "..."}
+    // rather than an object keyed by name or hex hash. Smaller and quicker to parse
+    // for bulk pipelines that don't need names. Rebuild accepts either form.
+    pub fn with_numeric_hashes(mut self, enabled: bool) -> Self {
+        self.numeric_hashes = enabled;
+        self
+    }
+
     pub fn convert(&self, data: &[u8], meta_json: String) -> LangResult<LocrJson> {
+        let meta = parse_meta(&meta_json, data)?;
         let mut buf = ByteReader::new(data, Endianness::Little);
 
-        let is_locr_v2 = if self.version != Version::H2016 {
-            buf.read::<u8>()?;
-            true
-        } else {
-            false
-        };
+        self.read_version(&mut buf)?;
 
         let mut j = LocrJson {
             schema: "https://tonytools.win/schemas/locr.schema.json".into(),
@@ -76,45 +270,227 @@ impl LOCR {
             j.symmetric = Some(true);
         }
 
+        let offsets = self.read_offsets(&mut buf)?;
+
+        // Each language is self-contained at its own offset, so they are decoded
+        // in parallel and then inserted in language map order.
+        let languages = offsets
+            .par_iter()
+            .map(|offset| self.read_language(buf.clone(), *offset))
+            .collect::<LangResult<Vec<_>>>()?;
+
+        for (language, strings) in std::iter::zip(&self.lang_map, languages) {
+            j.languages.insert(language.clone(), strings);
+        }
+
+        j.hash = meta.hash_path.unwrap_or(meta.hash_value);
+
+        Ok(j)
+    }
+
+    // Same output as serializing the result of convert, but each language is written
+    // out as soon as it's decoded so only one is held at a time. Meant for batch
+    // extracting very large LOCRs, the writer should be buffered.
+    pub fn convert_to_writer<W: Write>(
+        &self,
+        data: &[u8],
+        meta_json: String,
+        mut writer: W,
+    ) -> LangResult<()> {
+        let meta = parse_meta(&meta_json, data)?;
+        let mut buf = ByteReader::new(data, Endianness::Little);
+
+        self.read_version(&mut buf)?;
+
+        let offsets = self.read_offsets(&mut buf)?;
+
+        write!(writer, "{{\"$schema\":\"https://tonytools.win/schemas/locr.schema.json\",\"hash\":")?;
+        serde_json::to_writer(&mut writer, &meta.hash_path.unwrap_or(meta.hash_value))?;
+        if self.symmetric && self.version == Version::H2016 {
+            write!(writer, ",\"symmetric\":true")?;
+        }
+        write!(writer, ",\"languages\":{{")?;
+
+        for (i, (language, offset)) in std::iter::zip(&self.lang_map, offsets).enumerate() {
+            if i != 0 {
+                write!(writer, ",")?;
+            }
+
+            let strings = self.read_language(buf.clone(), offset)?;
+            serde_json::to_writer(&mut writer, language)?;
+            write!(writer, ":")?;
+            serde_json::to_writer(&mut writer, &strings)?;
+        }
+
+        write!(writer, "}}}}")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // Total lines across all languages, taken from the counts without decrypting
+    // anything, so it's cheap enough to use for stats.
+    pub fn line_count(&self, data: &[u8]) -> LangResult<usize> {
+        Ok(self.line_counts(data)?.iter().map(|(_, count)| count).sum())
+    }
+
+    // The lines in each language the file has, read the same way as line_count.
+    // Empty languages are included with a count of 0.
+    pub fn line_counts(&self, data: &[u8]) -> LangResult<Vec<(String, usize)>> {
+        let mut buf = ByteReader::new(data, Endianness::Little);
+
+        self.read_version(&mut buf)?;
+
+        let mut counts = Vec::new();
+        for (language, offset) in std::iter::zip(&self.lang_map, self.read_offsets(&mut buf)?) {
+            let count = match offset {
+                u32::MAX => 0,
+                offset => {
+                    buf.seek(offset as usize)?;
+                    buf.read::<u32>()?.inner() as usize
+                }
+            };
+            counts.push((language.clone(), count));
+        }
+
+        Ok(counts)
+    }
+
+    // Raw hashes that share a JSON key with another line in the same language, with
+    // the languages that happens in. Convert keeps only one line per key, so the
+    // rest would be lost on a round trip. This happens when a hash list name is the
+    // same as another line's hex fallback, or when a file repeats a hash.
+    pub fn detect_key_collisions(
+        &self,
+        data: &[u8],
+        meta_json: String,
+    ) -> LangResult<Vec<(u32, Vec<String>)>> {
+        parse_meta(&meta_json, data)?;
+        let mut buf = ByteReader::new(data, Endianness::Little);
+
+        self.read_version(&mut buf)?;
+
+        let mut collisions: IndexMap<u32, Vec<String>> = IndexMap::new();
+        for (language, offset) in std::iter::zip(&self.lang_map, self.read_offsets(&mut buf)?) {
+            if offset == u32::MAX {
+                continue;
+            }
+
+            buf.seek(offset as usize)?;
+            let mut keys: IndexMap<String, Vec<u32>> = IndexMap::new();
+            for _ in 0..buf.read::<u32>()?.inner() {
+                let hash = buf.read::<u32>()?.inner();
+                buf.read_sized_vec::<u8>(None)?;
+                buf.seek(buf.cursor() + 1)?; // Skip null terminator
+
+                keys.entry(self.line_key(hash)).or_default().push(hash);
+            }
+
+            for hash in keys.into_values().filter(|hashes| hashes.len() > 1).flatten() {
+                let languages = collisions.entry(hash).or_default();
+                if languages.last() != Some(language) {
+                    languages.push(language.clone());
+                }
+            }
+        }
+
+        Ok(collisions.into_iter().collect())
+    }
+
+    // The key a line is converted under, its name or the hex hash.
+    fn line_key(&self, hash: u32) -> String {
+        match self.hashlist.lines.get_by_left(&hash) {
+            Some(name) => name.clone(),
+            None => u32_to_hex(hash),
+        }
+    }
+
+    // Later games start with a version byte of 0, H2016 goes straight into the
+    // offset table. Its first byte is only 0 for a multiple of 64 languages, so
+    // a 0 there means the LOCR is from a later game. The other way round is caught
+    // by the offset check in read_offsets.
+    fn read_version(&self, buf: &mut ByteReader) -> LangResult<()> {
         let cursor = buf.cursor();
-        let num_languages = ((buf.read::<u32>()?.inner() - is_locr_v2 as u32) / 4) as usize;
+        let version = buf.read::<u8>()?.inner();
+
+        match self.version {
+            Version::H2016 if version == 0 => Err(LangError::UnsupportedVersion),
+            Version::H2016 => Ok(buf.seek(cursor)?),
+            _ => Ok(()),
+        }
+    }
+
+    fn read_offsets(&self, buf: &mut ByteReader) -> LangResult<Vec<u32>> {
+        // Empty languages are u32::MAX, the first one with lines points just past
+        // the table. So that gives the table's size, and also says whether the table
+        // follows a version byte. H2016 LOCRs have none, later games always do, and
+        // a mismatch means the file is from the other layout. If every language is
+        // empty the table is the rest of the file.
+        let cursor = buf.cursor();
+        let mut table_end = None;
+        while table_end.is_none() && buf.len() >= 4 && buf.cursor() - cursor < self.lang_map.len() * 4 {
+            let offset = buf.read::<u32>()?.inner();
+            if offset != u32::MAX {
+                table_end = Some(offset as usize);
+            }
+        }
+
+        let table_size = table_end
+            .unwrap_or(buf.cursor() + buf.len())
+            .checked_sub(cursor)
+            .filter(|size| size % 4 == 0)
+            .ok_or(LangError::UnsupportedVersion)?;
+
+        let num_languages = table_size / 4;
         if num_languages > self.lang_map.len() {
             return Err(LangError::InvalidLanguageMap);
         }
         buf.seek(cursor)?;
 
-        let offsets = buf.read_n::<u32>(num_languages)?.flatten();
-        for (i, offset) in offsets.iter().enumerate() {
-            let language = self.lang_map.get(i).expect("Something went wrong");
-            j.languages.insert(language.clone(), Map::new().into());
+        Ok(buf.read_n::<u32>(num_languages)?.flatten())
+    }
+
+    fn read_language(&self, mut buf: ByteReader, offset: u32) -> LangResult<Value> {
+        let mut strings = Map::new();
+        let mut lines = Vec::new();
 
-            if *offset == u32::MAX {
-                continue;
-            }
-            buf.seek(*offset as usize)?;
+        if offset != u32::MAX {
+            buf.seek(offset as usize)?;
 
             for _ in 0..buf.read::<u32>()?.inner() {
                 let hash_num = buf.read::<u32>()?.inner();
-                let hex: String = format!("{:08X}", hash_num);
-                let hash = self.hashlist.lines.get_by_left(&hash_num).unwrap_or(&hex);
-                let str_data = buf.read_sized_vector::<u8>()?.flatten();
+                let str_data = buf.read_sized_vec::<u8>(None)?;
                 buf.seek(buf.cursor() + 1)?; // Skip null terminator
 
-                j.languages[language][hash] = match self.symmetric {
-                    true => symmetric_decrypt(str_data)?.into(),
-                    false => xtea_decrypt(str_data)?.into(),
+                // Symmetric strings have no padding, so only TrimAll changes them.
+                let str = match (self.symmetric, self.string_policy) {
+                    (true, StringPolicy::TrimAll) => {
+                        self.string_policy.decoded(symmetric_decrypt(str_data)?)
+                    }
+                    (true, _) => symmetric_decrypt(str_data)?,
+                    (false, policy) => policy.decoded(xtea_decrypt_raw(str_data)?),
+                };
+
+                if self.numeric_hashes {
+                    lines.push(json!({ "hash": hash_num, "text": str }));
+                } else {
+                    strings.insert(self.line_key(hash_num), str.into());
                 }
             }
         }
 
-        let meta: rpkg::ResourceMeta = serde_json::from_str(meta_json.as_str())?;
-        j.hash = meta.hash_path.unwrap_or(meta.hash_value);
+        Ok(match self.numeric_hashes {
+            true => lines.into(),
+            false => strings.into(),
+        })
+    }
 
-        Ok(j)
+    pub fn normalize(&self, json: &str) -> LangResult<String> {
+        normalize::<LocrJson>(json)
     }
 
     pub fn rebuild(&self, json: String) -> LangResult<Rebuilt> {
-        let json: LocrJson = serde_json::from_str(&json)?;
+        let json: LocrJson = parse_json(&json)?;
         let mut symmetric = self.symmetric;
 
         if json.symmetric.is_some_and(|b| b) && self.version == Version::H2016 {
@@ -132,10 +508,18 @@ impl LOCR {
         buf.write_vec(vec![0; json.languages.len()]);
 
         for strings in json.languages.values() {
-            if !strings.is_object() {
-                return Err(LangError::InvalidInput);
-            }
-            let strings = strings.as_object().unwrap();
+            let strings = match strings {
+                Value::Object(strings) => strings
+                    .iter()
+                    .map(|(hash, str)| {
+                        let hash = resolve_hash(&self.hashlist.lines, hash, self.collision_policy)?;
+                        Ok((hash, str.as_str().ok_or(LangError::InvalidInput)?))
+                    })
+                    .collect::<LangResult<Vec<_>>>()?,
+                Value::Array(strings) => strings.iter().map(numeric_line).collect::<LangResult<Vec<_>>>()?,
+                Value::Null => Vec::new(),
+                _ => return Err(LangError::InvalidInput),
+            };
 
             if strings.is_empty() {
                 buf.write(u32::MAX, offset)?;
@@ -148,30 +532,223 @@ impl LOCR {
 
             buf.append(strings.len() as u32);
             for (hash, str) in strings {
-                if !str.is_string() {
-                    return Err(LangError::InvalidInput);
-                }
-                let str = str.as_str().unwrap();
+                let str = self.string_policy.encoded(str);
 
-                buf.append(*self.hashlist.lines.get_by_right(hash).unwrap_or(
-                    &u32::from_str_radix(hash, 16).unwrap_or(crc32fast::hash(hash.as_bytes())),
-                ));
+                buf.append(hash);
                 buf.write_sized_vec(match symmetric {
                     true => symmetric_encrypt(str.as_bytes().to_vec()),
-                    false => xtea_encrypt(str),
+                    false => xtea_encrypt(&str),
                 });
                 buf.append::<u8>(0);
             }
         }
 
-        Ok(Rebuilt {
-            file: buf.buf(),
-            meta: serde_json::to_string(&ResourceMeta::new(
+        // At least one language offset, after the version byte for non-H2016 LOCRs.
+        check_min_size(buf.len(), 4 + (self.version != Version::H2016) as usize)?;
+
+        Rebuilt::new(
+            buf.buf(),
+            &ResourceMeta::new(
                 json.hash,
                 buf.len() as u32,
                 "LOCR".into(),
                 IndexMap::new(),
-            ))?,
-        })
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bimap::BiMap;
+
+    fn hashlist() -> HashList {
+        HashList {
+            tags: BiMap::new(),
+            switches: BiMap::new(),
+            lines: BiMap::new(),
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn symmetric_strings_keep_nuls_unless_trimming_all() {
+        let json = json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "symmetric": true,
+            "languages": {"xx": {"0000BEEF": "Hi \0"}}
+        });
+
+        let text = |policy| {
+            let locr = LOCR::new(hashlist(), Version::H2016, None, true)
+                .unwrap()
+                .with_string_policy(policy);
+            let rebuilt = locr.rebuild(json.to_string()).unwrap();
+            let json = locr.convert(&rebuilt.file, rebuilt.meta).unwrap();
+            json.languages["xx"]["0000BEEF"].clone()
+        };
+
+        assert_eq!(text(StringPolicy::default()), "Hi \u{0}");
+        assert_eq!(text(StringPolicy::Raw), "Hi \u{0}");
+        assert_eq!(text(StringPolicy::TrimAll), "Hi");
+    }
+
+    #[test]
+    fn template_skips_malformed_lines() {
+        let json: LocrJson = serde_json::from_str(
+            r#"{
+                "$schema": "",
+                "hash": "00123456789ABCDE",
+                "languages": {
+                    "en": {"0000BEEF": "Hello"},
+                    "fr": [{"hash": 48879, "text": "Bonjour"}, 5, "text", null]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let template = json.template();
+        assert_eq!(template.languages["en"], json!({"0000BEEF": ""}));
+        assert_eq!(
+            template.languages["fr"],
+            json!([{"hash": 48879, "text": ""}, 5, "text", null])
+        );
+    }
+
+    #[test]
+    fn csv_round_trips_tricky_text() {
+        let tricky = "Hello, \"Agent 47\"\r\nGoodbye,\nfor now";
+        let json: LocrJson = serde_json::from_value(json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "languages": {
+                "en": {"0000BEEF": tricky, "0000CAFE": "plain"},
+                "fr": {"0000BEEF": "Bonjour"}
+            }
+        }))
+        .unwrap();
+
+        let mut merged = json.template();
+        merged.merge_csv(&json.to_csv()).unwrap();
+        assert_eq!(merged.flatten(), json.flatten());
+
+        let mut unknown = json.template();
+        let csv = "hash,language,text\r\n0000BEEF,de,Hallo\r\n";
+        assert!(matches!(
+            unknown.merge_csv(csv),
+            Err(LangError::InvalidLanguageMap)
+        ));
+        assert_eq!(unknown.languages["en"]["0000BEEF"], "");
+    }
+
+    #[test]
+    fn duplicate_hashes_are_rejected() {
+        let json = r#"{
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "languages": {"xx": {"0000BEEF": "Hi", "0000BEEF": "Hello"}}
+        }"#;
+
+        let locr = LOCR::new(hashlist(), Version::H3, None, false).unwrap();
+        assert!(matches!(
+            locr.rebuild(json.to_string()),
+            Err(LangError::DuplicateKey { key }) if key == "0000BEEF"
+        ));
+    }
+
+    #[test]
+    fn raw_strings_keep_their_encrypted_length() {
+        let json = json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "languages": {"xx": {"0000BEEF": "Subtitle\0\0\0\0\0\0\0\0", "0000CAFE": "Hi"}}
+        });
+
+        let locr = LOCR::new(hashlist(), Version::H3, None, false)
+            .unwrap()
+            .with_string_policy(StringPolicy::Raw);
+        let rebuilt = locr.rebuild(json.to_string()).unwrap();
+        let converted = locr.convert(&rebuilt.file, rebuilt.meta.clone()).unwrap();
+        assert_eq!(
+            converted.languages["xx"]["0000BEEF"],
+            "Subtitle\0\0\0\0\0\0\0\0"
+        );
+
+        let json = serde_json::to_string(&converted).unwrap();
+        assert_eq!(locr.rebuild(json).unwrap().file, rebuilt.file);
+    }
+
+    #[test]
+    fn null_and_empty_languages_round_trip() {
+        let round_trip = |version, languages: Value| {
+            let json = json!({"$schema": "", "hash": "00123456789ABCDE", "languages": languages});
+            let locr = LOCR::new(hashlist(), version, None, false).unwrap();
+            let rebuilt = locr.rebuild(json.to_string()).unwrap();
+            Value::Object(locr.convert(&rebuilt.file, rebuilt.meta).unwrap().languages)
+        };
+
+        for version in [Version::H2016, Version::H3] {
+            assert_eq!(
+                round_trip(version, json!({"xx": null, "en": {"0000BEEF": "Hi"}})),
+                json!({"xx": {}, "en": {"0000BEEF": "Hi"}})
+            );
+            assert_eq!(
+                round_trip(
+                    version,
+                    json!({"xx": {"0000BEEF": "Hi"}, "en": {}, "fr": null})
+                ),
+                json!({"xx": {"0000BEEF": "Hi"}, "en": {}, "fr": {}})
+            );
+            assert_eq!(
+                round_trip(version, json!({"xx": null, "en": null})),
+                json!({"xx": {}, "en": {}})
+            );
+        }
+    }
+
+    #[test]
+    fn other_layouts_are_rejected() {
+        let json = json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "languages": {"xx": {"0000BEEF": "Hi"}, "en": {"0000CAFE": "Hello"}}
+        });
+
+        let h2016 = LOCR::new(hashlist(), Version::H2016, None, false).unwrap();
+        let h3 = LOCR::new(hashlist(), Version::H3, None, false).unwrap();
+        for (from, to) in [(&h2016, &h3), (&h3, &h2016)] {
+            let rebuilt = from.rebuild(json.to_string()).unwrap();
+            assert!(matches!(
+                to.convert(&rebuilt.file, rebuilt.meta.clone()),
+                Err(LangError::UnsupportedVersion)
+            ));
+            assert!(matches!(
+                to.line_count(&rebuilt.file),
+                Err(LangError::UnsupportedVersion)
+            ));
+        }
+    }
+
+    #[test]
+    fn line_counts_match_the_converted_json() {
+        let json = json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "languages": {"xx": {"0000BEEF": "Hi"}, "en": {}, "fr": {"0000CAFE": "Salut", "0000F00D": "Oui"}}
+        });
+
+        for version in [Version::H2016, Version::H3] {
+            let locr = LOCR::new(hashlist(), version, None, false).unwrap();
+            let rebuilt = locr.rebuild(json.to_string()).unwrap();
+            let converted = locr.convert(&rebuilt.file, rebuilt.meta).unwrap();
+
+            assert_eq!(
+                locr.line_counts(&rebuilt.file).unwrap(),
+                converted.line_counts()
+            );
+            assert_eq!(locr.line_count(&rebuilt.file).unwrap(), 3);
+        }
     }
 }
@@ -1,14 +1,43 @@
-use std::{error::Error, num::ParseIntError, string::FromUtf8Error};
+use std::{
+    borrow::Cow,
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fs,
+    io::{self, Write},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+    str::FromStr,
+    string::FromUtf8Error,
+};
 
+use base64::{prelude::BASE64_STANDARD, Engine};
 use bitchomp::{ByteReaderError, ByteWriterError};
+use indexmap::IndexMap;
+use log::debug;
+use serde::{
+    de::{DeserializeOwned, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::{Map, Value};
 use strum_macros::Display;
 
+#[cfg(feature = "rpkg")]
+use crate::util::rpkg::PackageResource;
+use crate::{
+    util::{
+        rpkg::{ResourceDependency, ResourceMeta},
+        vec_of_strings,
+    },
+    Version,
+};
+
 pub mod clng;
 pub mod ditl;
 pub mod dlge;
 pub mod hashlist;
 pub mod locr;
 pub mod rtlv;
+pub mod search;
 
 #[derive(Debug, Display)]
 pub enum LangError {
@@ -19,10 +48,41 @@ pub enum LangError {
     ByteReaderError(ByteReaderError),
     ByteWriterError(ByteWriterError),
     Utf8Error(FromUtf8Error),
+    IoError(io::Error),
+    Base64Error(base64::DecodeError),
     InvalidContainer(u8),
-    InvalidReference(u8),
+    // The container type or index that was referenced, indices are 12 bits.
+    InvalidReference(u16),
     ParseIntError(ParseIntError),
     InvalidInput,
+    #[strum(to_string = "MissingWeight({wav})")]
+    MissingWeight { wav: String },
+    #[strum(to_string = "InvalidLanguageValue({wav}, {language})")]
+    InvalidLanguageValue { wav: String, language: String },
+    #[strum(to_string = "MissingCases({container})")]
+    MissingCases { container: String },
+    #[strum(to_string = "HashCollision({name}, {existing})")]
+    HashCollision { name: String, existing: String },
+    #[strum(to_string = "DuplicateKey({key})")]
+    DuplicateKey { key: String },
+    #[strum(to_string = "InvalidCsv({line})")]
+    InvalidCsv { line: usize },
+    #[strum(to_string = "OutputTooSmall({size}, {minimum})")]
+    OutputTooSmall { size: usize, minimum: usize },
+    #[strum(to_string = "OrphanedContainers({count})")]
+    OrphanedContainers { count: usize },
+    #[strum(to_string = "UnresolvedDepend({tag})")]
+    UnresolvedDepend { tag: String },
+    #[strum(to_string = "MetaSizeMismatch({expected}, {actual})")]
+    MetaSizeMismatch { expected: u32, actual: usize },
+    // Another error with the file or other input it came from, see LangError::at.
+    // Adding this variant is a breaking change for exhaustive matches on LangError.
+    #[strum(to_string = "{source}: {error}")]
+    WithContext { source: String, error: Box<LangError> },
+    #[cfg(feature = "yaml")]
+    YamlError(serde_yaml::Error),
+    #[cfg(feature = "msgpack")]
+    MsgpackError(rmp_serde::encode::Error),
 }
 
 impl From<ByteReaderError> for LangError {
@@ -49,18 +109,600 @@ impl From<serde_json::Error> for LangError {
     }
 }
 
+#[cfg(feature = "yaml")]
+impl From<serde_yaml::Error> for LangError {
+    fn from(err: serde_yaml::Error) -> Self {
+        LangError::YamlError(err)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::encode::Error> for LangError {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        LangError::MsgpackError(err)
+    }
+}
+
+impl From<io::Error> for LangError {
+    fn from(err: io::Error) -> Self {
+        LangError::IoError(err)
+    }
+}
+
+impl From<base64::DecodeError> for LangError {
+    fn from(err: base64::DecodeError) -> Self {
+        LangError::Base64Error(err)
+    }
+}
+
 impl From<FromUtf8Error> for LangError {
     fn from(err: FromUtf8Error) -> Self {
         LangError::Utf8Error(err)
     }
 }
 
-impl Error for LangError {}
+impl LangError {
+    // Adds the file or other input the error came from, so callers working through
+    // many files don't have to track that themselves:
+    // `dlge.convert(&data, meta).map_err(|e| e.at(path.display()))`
+    pub fn at(self, source: impl std::fmt::Display) -> Self {
+        LangError::WithContext {
+            source: source.to_string(),
+            error: Box::new(self),
+        }
+    }
+
+    // The error without any context added by at, for matching on.
+    pub fn inner(&self) -> &LangError {
+        match self {
+            LangError::WithContext { error, .. } => error.inner(),
+            error => error,
+        }
+    }
+}
+
+impl Error for LangError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LangError::WithContext { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 pub type LangResult<T> = Result<T, LangError>;
 
+// The language resource types, displayed as their four-CC.
+#[derive(Debug, Display, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "tools", derive(clap::ValueEnum))]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Filetype {
+    CLNG,
+    DITL,
+    DLGE,
+    LOCR,
+    RTLV,
+}
+
+impl Filetype {
+    pub fn all() -> &'static [Self] {
+        &[Self::CLNG, Self::DITL, Self::DLGE, Self::LOCR, Self::RTLV]
+    }
+
+    // Matches any case, since extensions aren't always upper case.
+    pub fn from_four_cc(four_cc: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|filetype| filetype.four_cc().eq_ignore_ascii_case(four_cc))
+    }
+
+    // The file extension and the meta's resource type.
+    pub fn four_cc(self) -> &'static str {
+        match self {
+            Self::CLNG => "CLNG",
+            Self::DITL => "DITL",
+            Self::DLGE => "DLGE",
+            Self::LOCR => "LOCR",
+            Self::RTLV => "RTLV",
+        }
+    }
+}
+
+impl FromStr for Filetype {
+    type Err = LangError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        Filetype::from_four_cc(str).ok_or(LangError::InvalidInput)
+    }
+}
+
 #[derive(Debug)]
 pub struct Rebuilt {
     pub file: Vec<u8>,
     pub meta: String,
 }
+
+impl Rebuilt {
+    pub fn new(file: Vec<u8>, meta: &ResourceMeta) -> LangResult<Self> {
+        Ok(Rebuilt {
+            file,
+            meta: meta.to_json()?,
+        })
+    }
+
+    // CRC32 of the rebuilt file, useful for caching and deduplication.
+    pub fn content_hash(&self) -> u32 {
+        crc32fast::hash(&self.file)
+    }
+
+    // The meta is kept as a string so it can be written straight to disk,
+    // this parses it for callers that need to inspect or extend it.
+    pub fn meta_parsed(&self) -> LangResult<ResourceMeta> {
+        Ok(ResourceMeta::from_json(&self.meta)?)
+    }
+
+    // For handing the resource straight to an RPKG writer instead of to disk.
+    #[cfg(feature = "rpkg")]
+    pub fn to_package(&self) -> LangResult<PackageResource> {
+        self.meta_parsed()?
+            .to_package(self.file.clone())
+            .ok_or(LangError::InvalidInput)
+    }
+
+    // Writes the file to the path and the meta next to it as {path}.meta.JSON.
+    pub fn write_to(&self, out: &Path) -> io::Result<()> {
+        fs::write(out, &self.file)?;
+
+        let mut meta_path = out.as_os_str().to_owned();
+        meta_path.push(".meta.JSON");
+        fs::write(meta_path, &self.meta)
+    }
+}
+
+// What a converter's convert takes, either as the usual two files or bundled into
+// one JSON as {"data_base64": "...", "meta": {...}}. Without a meta path the meta
+// is looked for beside the data as {data_path}.meta.JSON.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ConvertInput {
+    Files {
+        data_path: PathBuf,
+        meta_path: Option<PathBuf>,
+    },
+    Bundled {
+        data_base64: String,
+        meta: Value,
+    },
+}
+
+impl ConvertInput {
+    pub fn from_json(json: &str) -> LangResult<Self> {
+        parse_json(json)
+    }
+
+    // The resource data and meta JSON, ready to pass to convert.
+    pub fn load(&self) -> LangResult<(Vec<u8>, String)> {
+        match self {
+            ConvertInput::Files {
+                data_path,
+                meta_path,
+            } => {
+                let meta_path = meta_path.clone().unwrap_or_else(|| {
+                    let mut path = data_path.as_os_str().to_owned();
+                    path.push(".meta.JSON");
+                    path.into()
+                });
+
+                let data =
+                    fs::read(data_path).map_err(|e| LangError::from(e).at(data_path.display()))?;
+                let meta = fs::read_to_string(&meta_path)
+                    .map_err(|e| LangError::from(e).at(meta_path.display()))?;
+
+                Ok((data, meta))
+            }
+            ConvertInput::Bundled { data_base64, meta } => Ok((
+                BASE64_STANDARD.decode(data_base64)?,
+                serde_json::to_string(meta)?,
+            )),
+        }
+    }
+}
+
+// The depends of any of the language resources, with their flags. DLGE has its
+// wavs, ffxs, DITL and CLNG, DITL its soundtags and RTLV its videos, all of which
+// come straight from the meta. LOCR and CLNG don't reference anything.
+pub fn dependencies(meta_json: &str, data: &[u8]) -> LangResult<Vec<ResourceDependency>> {
+    let meta = parse_meta(meta_json, data)?;
+
+    match meta.hash_resource_type.parse()? {
+        Filetype::DLGE | Filetype::DITL | Filetype::RTLV => Ok(meta.hash_reference_data),
+        Filetype::LOCR | Filetype::CLNG => Ok(Vec::new()),
+    }
+}
+
+// Writes rebuilt resources in the layout RPKG builders and mod frameworks expect,
+// {out}/{chunk}/{hash}.{type} with the meta beside it as {hash}.{type}.meta.JSON.
+// The name is taken from the meta so path hashes are already resolved. Returns
+// the paths of the written resource files.
+pub fn deploy(out: &Path, chunk: &str, resources: &[Rebuilt]) -> LangResult<Vec<PathBuf>> {
+    let dir = out.join(chunk);
+    fs::create_dir_all(&dir)?;
+
+    let mut paths = Vec::new();
+    for rebuilt in resources {
+        let meta = rebuilt.meta_parsed()?;
+        let path = dir.join(format!("{}.{}", meta.hash_value, meta.hash_resource_type));
+
+        rebuilt.write_to(&path)?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+// How decrypted strings are cleaned up on convert. TrimNul removes the XTEA
+// padding, Raw keeps it for byte-exact rebuilds and TrimAll also normalises
+// line endings and trailing whitespace for cleaner diffs.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum StringPolicy {
+    Raw,
+    #[default]
+    TrimNul,
+    TrimAll,
+}
+
+impl StringPolicy {
+    pub(crate) fn decoded(self, str: String) -> String {
+        match self {
+            StringPolicy::Raw => str,
+            StringPolicy::TrimNul => str.trim_matches(char::from(0)).to_string(),
+            StringPolicy::TrimAll => Self::normalise(&str),
+        }
+    }
+
+    // TrimAll is applied on rebuild too, otherwise edited strings would bring
+    // back what convert trimmed. The other policies write strings unchanged.
+    pub(crate) fn encoded(self, str: &str) -> Cow<'_, str> {
+        match self {
+            StringPolicy::TrimAll => Cow::Owned(Self::normalise(str)),
+            _ => Cow::Borrowed(str),
+        }
+    }
+
+    fn normalise(str: &str) -> String {
+        str.trim_matches(char::from(0))
+            .replace("\r\n", "\n")
+            .split('\n')
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim_end()
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+// The converted *Json structs are plain serde types, this writes them out in
+// any of the enabled formats.
+pub trait ToWriter: Serialize {
+    fn to_writer<W: Write>(&self, writer: W, format: OutputFormat) -> LangResult<()> {
+        match format {
+            OutputFormat::Json => serde_json::to_writer_pretty(writer, self)?,
+            #[cfg(feature = "yaml")]
+            OutputFormat::Yaml => serde_yaml::to_writer(writer, self)?,
+            #[cfg(feature = "msgpack")]
+            OutputFormat::MessagePack => {
+                let mut writer = writer;
+                rmp_serde::encode::write_named(&mut writer, self)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Lines that differ between two versions of a resource, keyed by the line's hash
+// (or wav name for DLGE) and language.
+#[derive(Serialize, Default, Debug, Clone, PartialEq)]
+pub struct LineDiff {
+    pub added: Vec<DiffLine>,
+    pub removed: Vec<DiffLine>,
+    pub changed: Vec<ChangedLine>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub key: String,
+    pub language: String,
+    pub text: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChangedLine {
+    pub key: String,
+    pub language: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl ToWriter for LineDiff {}
+
+impl LineDiff {
+    // Takes flattened (key, language, text) lines. Keys can repeat, wav names
+    // aren't unique, so repeats are matched up in the order they appear.
+    pub(crate) fn new(old: Vec<(String, String, String)>, new: Vec<(String, String, String)>) -> Self {
+        let mut diff = LineDiff::default();
+
+        let mut old_lines: IndexMap<(String, String), VecDeque<String>> = IndexMap::new();
+        for (key, language, text) in old {
+            old_lines.entry((key, language)).or_default().push_back(text);
+        }
+
+        for (key, language, text) in new {
+            match old_lines.get_mut(&(key.clone(), language.clone())).and_then(|texts| texts.pop_front()) {
+                Some(old) if old == text => {}
+                Some(old) => diff.changed.push(ChangedLine {
+                    key,
+                    language,
+                    old,
+                    new: text,
+                }),
+                None => diff.added.push(DiffLine { key, language, text }),
+            }
+        }
+
+        for ((key, language), texts) in old_lines {
+            for text in texts {
+                diff.removed.push(DiffLine {
+                    key: key.clone(),
+                    language: language.clone(),
+                    text,
+                });
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// Rebuild derives depend flags from where the depend is used. Convert records
+// the flags that differ from that so rebuild can reproduce them exactly.
+pub(crate) fn record_depend_flag(
+    flags: &mut Map<String, Value>,
+    depend: Option<&ResourceDependency>,
+    default: &str,
+) {
+    if let Some(depend) = depend.filter(|depend| !depend.flag.eq_ignore_ascii_case(default)) {
+        flags.insert(depend.hash.clone(), depend.flag.clone().into());
+    }
+}
+
+// The flag recorded for a depend on convert, or the default flag.
+pub(crate) fn depend_flag(flags: &Option<Map<String, Value>>, path: &str, default: String) -> String {
+    flags
+        .as_ref()
+        .and_then(|flags| flags.get(path))
+        .and_then(|flag| flag.as_str())
+        .map(|flag| flag.to_string())
+        .unwrap_or(default)
+}
+
+// The language map used by the game when no custom map is given.
+// DLGE differs for H2016, see dlge::default_lang_map.
+pub(crate) fn default_lang_map(version: Version) -> LangResult<Vec<String>> {
+    match version {
+        Version::H2016 | Version::H2 => Ok(vec_of_strings![
+            "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp", "tc"
+        ]),
+        Version::H3 => Ok(vec_of_strings![
+            "xx", "en", "fr", "it", "de", "es", "ru", "cn", "tc", "jp"
+        ]),
+        _ => Err(LangError::UnsupportedVersion),
+    }
+}
+
+// Parses a converted JSON file. Windows editors like Notepad save UTF-8 with a
+// BOM, which serde_json rejects, so it's skipped.
+// Duplicate keys are rejected rather than serde_json's last one wins, as they're
+// usually left over from merging files and one of the values would be lost.
+pub(crate) fn parse_json<T: DeserializeOwned>(json: &str) -> LangResult<T> {
+    let json = json.strip_prefix('\u{FEFF}').unwrap_or(json);
+
+    if let DuplicateKey(Some(key)) = serde_json::from_str(json)? {
+        return Err(LangError::DuplicateKey { key });
+    }
+
+    Ok(serde_json::from_str(json)?)
+}
+
+// Walks any JSON value, holding the first key repeated within one object.
+struct DuplicateKey(Option<String>);
+
+impl<'de> Deserialize<'de> for DuplicateKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DuplicateKeyVisitor)
+    }
+}
+
+struct DuplicateKeyVisitor;
+
+impl<'de> Visitor<'de> for DuplicateKeyVisitor {
+    type Value = DuplicateKey;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(DuplicateKey(None))
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(DuplicateKey(None))
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(DuplicateKey(None))
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(DuplicateKey(None))
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(DuplicateKey(None))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(DuplicateKey(None))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut duplicate = None;
+        while let Some(DuplicateKey(found)) = seq.next_element()? {
+            duplicate = duplicate.or(found);
+        }
+
+        Ok(DuplicateKey(duplicate))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut keys = HashSet::new();
+        let mut duplicate = None;
+        while let Some(key) = map.next_key::<String>()? {
+            let DuplicateKey(found) = map.next_value()?;
+            if duplicate.is_none() && !keys.insert(key.clone()) {
+                duplicate = Some(key);
+            }
+            duplicate = duplicate.or(found);
+        }
+
+        Ok(DuplicateKey(duplicate))
+    }
+}
+
+// A rebuilt file smaller than the smallest valid file for its format can't be
+// loaded by the game, and almost always comes from a JSON missing its content.
+pub(crate) fn check_min_size(size: usize, minimum: usize) -> LangResult<()> {
+    if size < minimum {
+        return Err(LangError::OutputTooSmall { size, minimum });
+    }
+
+    Ok(())
+}
+
+// Parses the meta for a resource being converted. A meta whose size doesn't match
+// the data was most likely paired with the wrong file, which would otherwise give
+// confusing errors later on. A zero size is treated as unknown.
+pub(crate) fn parse_meta(meta_json: &str, data: &[u8]) -> LangResult<ResourceMeta> {
+    let meta = ResourceMeta::from_json(meta_json)?;
+    if meta.hash_size_final == 0 {
+        debug!("Meta for {} has no size, not checking it", meta.hash_value);
+    } else if meta.hash_size_final as usize != data.len() {
+        return Err(LangError::MetaSizeMismatch {
+            expected: meta.hash_size_final,
+            actual: data.len(),
+        });
+    }
+
+    Ok(meta)
+}
+
+// Parses the JSON into its typed struct and writes it back out, giving
+// a stable formatting for hand-edited files.
+pub(crate) fn normalize<T: Serialize + DeserializeOwned>(json: &str) -> LangResult<String> {
+    let json: T = parse_json(json)?;
+    Ok(serde_json::to_string_pretty(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_wraps_and_strips() {
+        let error = LangError::InvalidInput.at("dialog.dlge").at("chunk0");
+
+        assert_eq!(error.to_string(), "chunk0: dialog.dlge: InvalidInput");
+        assert!(matches!(error.inner(), LangError::InvalidInput));
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "dialog.dlge: InvalidInput"
+        );
+        assert!(LangError::InvalidInput.source().is_none());
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        let duplicate = |json: &str| match parse_json::<serde_json::Value>(json) {
+            Err(LangError::DuplicateKey { key }) => Some(key),
+            Ok(_) => None,
+            Err(e) => panic!("unexpected error {e}"),
+        };
+
+        let locr = r#"{"hash": "", "languages": {"en": {"0000BEEF": "Hi"}, "en": {}}}"#;
+        assert_eq!(duplicate(locr).as_deref(), Some("en"));
+        assert_eq!(
+            duplicate(r#"[{"a": 1}, {"b": [{"c": 1, "c": 2}]}]"#).as_deref(),
+            Some("c")
+        );
+        assert_eq!(duplicate("\u{FEFF}{\"a\": 1, \"b\": {\"a\": 2}}"), None);
+    }
+
+    #[test]
+    fn convert_input_shapes() {
+        let json = r#"{"$schema": "", "hash": "00123456789ABCDE", "languages": {"xx": true}}"#;
+        let rebuilt = clng::CLNG::new(Version::H3, None)
+            .unwrap()
+            .rebuild(json.into())
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("tonytools-input-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let data_path = dir.join("00123456789ABCDE.CLNG");
+        rebuilt.write_to(&data_path).unwrap();
+
+        let meta_path = dir.join("00123456789ABCDE.CLNG.meta.JSON");
+        let meta: Value = serde_json::from_str(&rebuilt.meta).unwrap();
+        let inputs = [
+            serde_json::json!({"data_path": data_path}),
+            serde_json::json!({"data_path": data_path, "meta_path": meta_path}),
+            serde_json::json!({"data_base64": BASE64_STANDARD.encode(&rebuilt.file), "meta": meta}),
+        ];
+        for input in inputs {
+            let (data, meta) = ConvertInput::from_json(&input.to_string())
+                .unwrap()
+                .load()
+                .unwrap();
+            assert_eq!(data, rebuilt.file);
+            assert_eq!(
+                parse_meta(&meta, &data).unwrap().hash_value,
+                "00123456789ABCDE"
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        let bad = ConvertInput::from_json(r#"{"data_base64": "not base64!", "meta": {}}"#);
+        assert!(matches!(
+            bad.unwrap().load(),
+            Err(LangError::Base64Error(_))
+        ));
+        assert!(matches!(
+            ConvertInput::from_json(r#"{"data": ""}"#),
+            Err(LangError::JsonError(_))
+        ));
+    }
+}
@@ -1,18 +1,40 @@
-use bitchomp::{ByteReader, ByteWriter, Endianness, ChompFlatten};
+use bitchomp::{ByteReader, ByteWriter, ChompFlatten, Endianness};
 use indexmap::IndexMap;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
 
 use crate::{
     util::{
-        cipher::{xtea_decrypt, xtea_encrypt},
-        rpkg::{compute_hash, is_valid_hash, ResourceMeta},
-        vec_of_strings,
+        cipher::{xtea_decrypt_raw, xtea_encrypt},
+        hex::{u64_from_hex, u64_to_hex},
+        rpkg::{compute_hash, ResourceMeta},
     },
     Version,
 };
 
-use super::{LangError, LangResult, Rebuilt};
+use super::{
+    check_min_size, default_lang_map, depend_flag, normalize, parse_json, parse_meta, LangError,
+    LangResult, Rebuilt, StringPolicy, ToWriter,
+};
+
+// The BIN1 header and the four vector pointers.
+const MIN_SIZE: usize = 0x70;
+
+const RELOCATION_SEGMENT: u32 = 0x12EBA5ED;
+
+// Videos are given either as the RID the RTLV stores, any 16 hex digits, or as
+// the path of the video resource. The RID is that resource's hash, so anything
+// else is hashed like a resource path. Only exactly 16 digits are taken as a RID.
+fn video_rid(video: &str) -> LangResult<u64> {
+    let rid = match video.len() {
+        16 => u64_from_hex(video),
+        _ => None,
+    };
+
+    rid.or_else(|| u64_from_hex(&compute_hash(video)))
+        .ok_or(LangError::InvalidInput)
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RtlvJson {
@@ -23,6 +45,27 @@ pub struct RtlvJson {
     langmap: Option<String>,
     videos: Map<String, serde_json::Value>,
     subtitles: Map<String, serde_json::Value>,
+    #[serde(rename = "dependFlags", skip_serializing_if = "Option::is_none")]
+    depend_flags: Option<Map<String, serde_json::Value>>,
+}
+
+impl ToWriter for RtlvJson {}
+
+impl RtlvJson {
+    // RTLV subtitles have no timing, so the single cue covers the longest time
+    // both formats allow which spans the whole video.
+    pub fn to_srt(&self, lang: &str) -> Option<String> {
+        let subtitle = self.subtitles.get(lang)?.as_str()?;
+        Some(format!("1\n00:00:00,000 --> 99:59:59,999\n{}\n", subtitle))
+    }
+
+    pub fn to_vtt(&self, lang: &str) -> Option<String> {
+        let subtitle = self.subtitles.get(lang)?.as_str()?;
+        Some(format!(
+            "WEBVTT\n\n00:00:00.000 --> 99:59:59.999\n{}\n",
+            subtitle
+        ))
+    }
 }
 
 // This is a knockoff of the ZHMSerializer from ZHMTools.
@@ -33,25 +76,60 @@ struct GameRtlv {
     video_rids: Vec<u64>,
     subtitle_languages: Vec<String>,
     subtitles: Vec<String>,
+    // Every pointer in the data, relative to its start. Read ones are the pointers
+    // that were followed, which is what serialize writes for the same layout.
     relocations: Vec<u32>,
 }
 
 impl GameRtlv {
-    pub fn read(buf: &mut ByteReader) -> LangResult<Self> {
-        let video_languages = Self::read_string_vec(buf)?;
-        let video_rids = Self::read_rid_vec(buf)?;
-        let subtitle_languages = Self::read_string_vec(buf)?;
-        let subtitles = Self::read_string_vec(buf)?;
+    pub fn read(buf: &mut ByteReader, policy: StringPolicy) -> LangResult<Self> {
+        let mut relocations = Vec::new();
+        let video_languages = Self::read_string_vec(buf, StringPolicy::TrimNul, &mut relocations)?;
+        let video_rids = Self::read_rid_vec(buf, &mut relocations)?;
+        let subtitle_languages =
+            Self::read_string_vec(buf, StringPolicy::TrimNul, &mut relocations)?;
+        let subtitles = Self::read_string_vec(buf, policy, &mut relocations)?;
+        relocations.sort();
+
         Ok(GameRtlv {
             video_languages,
             video_rids,
             subtitle_languages,
             subtitles,
-            relocations: Vec::new(),
+            relocations,
         })
     }
 
-    pub fn serialize(&mut self) -> LangResult<Vec<u8>> {
+    // The relocation segment of a whole BIN1 file. Any other segments are skipped,
+    // a file without one has no relocations.
+    fn read_relocations(data: &[u8]) -> LangResult<Vec<u32>> {
+        let (Some(segments), Some(size)) = (data.get(0x06), data.get(0x08..0x0C)) else {
+            return Err(LangError::InvalidInput);
+        };
+
+        let mut buf = ByteReader::new(data, Endianness::Little);
+        buf.seek(0x10 + u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize)?;
+
+        for _ in 0..*segments {
+            let kind: u32 = buf.read()?.inner();
+            let size: u32 = buf.read()?.inner();
+            let next = buf.cursor() + size as usize;
+
+            if kind == RELOCATION_SEGMENT {
+                let count: u32 = buf.read()?.inner();
+                return (0..count).map(|_| Ok(buf.read::<u32>()?.inner())).collect();
+            }
+
+            buf.seek(next)?;
+        }
+
+        Ok(Vec::new())
+    }
+
+    // With a reference table, it's written as-is when it still covers every pointer
+    // and nothing in it is past the data, which is the case when the layout is
+    // unchanged. Otherwise only the pointers are written.
+    pub fn serialize(&mut self, reference: Option<&[u32]>) -> LangResult<Vec<u8>> {
         let mut buf = ByteWriter::new(Endianness::Little);
 
         // Write bytes for the pointers we change later.
@@ -112,8 +190,14 @@ impl GameRtlv {
         bin.append(0_u32);
 
         // Write relocations
-        buf.append(0x12EBA5ED_u32);
         self.relocations.sort();
+        if let Some(reference) = reference {
+            let covered = self.relocations.iter().all(|r| reference.contains(r));
+            if covered && reference.iter().all(|r| (*r as usize) + 8 <= buf.len()) {
+                self.relocations = reference.to_vec();
+            }
+        }
+        buf.append(RELOCATION_SEGMENT);
         buf.append(((self.relocations.len() * 4) + 4) as u32);
         buf.write_sized_vec(self.relocations.clone());
 
@@ -157,23 +241,29 @@ impl GameRtlv {
         Ok(buf.buf())
     }
 
-    fn read_string_vec(buf: &mut ByteReader) -> LangResult<Vec<String>> {
+    fn read_string_vec(
+        buf: &mut ByteReader,
+        policy: StringPolicy,
+        relocations: &mut Vec<u32>,
+    ) -> LangResult<Vec<String>> {
         let next = buf.cursor() + 24;
+        Self::push_vec_ptrs(buf.cursor(), relocations);
         let start: u64 = buf.read()?.inner();
         let end: u64 = buf.read()?.inner();
-        let size = (end - start) / 16;
+        let size = end.checked_sub(start).ok_or(LangError::InvalidInput)? / 16;
 
         buf.seek(start as usize)?;
 
         let mut vec: Vec<String> = Vec::new();
 
         for _ in 0..size {
+            relocations.push((buf.cursor() + 8) as u32);
             let len = buf.read::<u64>()?.inner() & !0x40000000;
             let ptr: u64 = buf.read()?.inner();
             let cursor = buf.cursor();
 
             buf.seek(ptr as usize)?;
-            vec.push(xtea_decrypt(buf.read_n(len as usize)?.flatten())?);
+            vec.push(policy.decoded(xtea_decrypt_raw(buf.read_n(len as usize)?.flatten())?));
 
             buf.seek(cursor)?;
         }
@@ -183,11 +273,12 @@ impl GameRtlv {
         Ok(vec)
     }
 
-    fn read_rid_vec(buf: &mut ByteReader) -> LangResult<Vec<u64>> {
+    fn read_rid_vec(buf: &mut ByteReader, relocations: &mut Vec<u32>) -> LangResult<Vec<u64>> {
         let cursor = buf.cursor() + 24;
+        Self::push_vec_ptrs(buf.cursor(), relocations);
         let start: u64 = buf.read()?.inner();
         let end: u64 = buf.read()?.inner();
-        let size = (end - start) / 8;
+        let size = end.checked_sub(start).ok_or(LangError::InvalidInput)? / 8;
 
         buf.seek(start as usize)?;
 
@@ -204,77 +295,167 @@ impl GameRtlv {
 
         Ok(vec)
     }
+
+    // A vector is a start, end and capacity pointer.
+    fn push_vec_ptrs(pos: usize, relocations: &mut Vec<u32>) {
+        let pos = pos as u32;
+        relocations.extend([pos, pos + 8, pos + 16]);
+    }
 }
 
 pub struct RTLV {
     lang_map: Vec<String>,
+    custom_langmap: bool,
+    string_policy: StringPolicy,
     depends: IndexMap<String, String>,
 }
 
 impl RTLV {
     pub fn new(version: Version, lang_map: Option<Vec<String>>) -> LangResult<Self> {
+        let custom_langmap = lang_map.is_some();
         let lang_map = if let Some(map) = lang_map {
             map
         } else {
-            match version {
-                Version::H2016 | Version::H2 => vec_of_strings![
-                    "xx", "en", "fr", "it", "de", "es", "ru", "mx", "br", "pl", "cn", "jp", "tc"
-                ],
-                Version::H3 => {
-                    vec_of_strings!["xx", "en", "fr", "it", "de", "es", "ru", "cn", "tc", "jp"]
-                }
-                _ => return Err(LangError::UnsupportedVersion),
-            }
+            default_lang_map(version)?
         };
 
         Ok(RTLV {
             lang_map,
+            custom_langmap,
+            string_policy: StringPolicy::default(),
             depends: IndexMap::new(),
         })
     }
 
+    pub fn with_string_policy(mut self, policy: StringPolicy) -> Self {
+        self.string_policy = policy;
+        self
+    }
+
     pub fn convert(&self, data: &[u8], meta_json: String) -> LangResult<RtlvJson> {
+        let meta = parse_meta(&meta_json, data)?;
         let mut buf = ByteReader::new(data, Endianness::Little);
 
-        if buf.read::<u32>()?.inner() != 0x314E4942 {
+        // The data starts after the 0x10 byte BIN1 header.
+        if data.len() < 0x10 || buf.read::<u32>()?.inner() != 0x314E4942 {
             return Err(LangError::InvalidInput);
         }
 
+        // It only matters for the warning below, which is skipped if the table
+        // can't be read.
+        let relocations = GameRtlv::read_relocations(data).ok();
+
         buf.rebase(0x10);
 
         let mut j = RtlvJson {
             schema: "https://tonytools.win/schemas/rtlv.schema.json".into(),
             hash: "".into(),
-            langmap: None,
+            langmap: if self.custom_langmap {
+                Some(self.lang_map.join(","))
+            } else {
+                None
+            },
             videos: Map::new(),
             subtitles: Map::new(),
+            depend_flags: None,
         };
 
-        let data = GameRtlv::read(&mut buf)?;
+        let data = GameRtlv::read(&mut buf, self.string_policy)?;
+
+        // A rebuild only writes relocations for the pointers we know about, so
+        // anything else in the table won't survive one. See rebuild_like.
+        if let Some(relocations) = relocations {
+            let extra = relocations
+                .iter()
+                .filter(|r| !data.relocations.contains(r))
+                .count();
+            let missing = data
+                .relocations
+                .iter()
+                .filter(|r| !relocations.contains(r))
+                .count();
+            if extra != 0 || missing != 0 {
+                warn!(
+                    "RTLV relocations differ from its pointers ({} extra, {} missing), a rebuild won't match",
+                    extra, missing
+                );
+            }
+        }
 
+        // Videos and subtitles are stored as separate language lists so either can
+        // have languages the other doesn't, but each list must match its values.
+        if data.video_languages.len() != data.video_rids.len()
+            || data.subtitle_languages.len() != data.subtitles.len()
+        {
+            return Err(LangError::InvalidInput);
+        }
+
+        let mut depend_flags = Map::new();
         for (lang, rid) in std::iter::zip(data.video_languages, data.video_rids) {
-            j.videos.insert(lang, format!("{:016X}", rid).into());
+            let rid = u64_to_hex(rid);
+
+            // A video rebuilt from its path has the path as the depend. The flag is
+            // recorded by RID either way, as that is how the video is converted.
+            if let Some(index) = self.lang_map.iter().position(|x| *x == lang) {
+                let default = format!("{:2X}", 0x80 + index);
+                let depend = meta
+                    .hash_reference_data
+                    .iter()
+                    .find(|d| d.hash == rid || compute_hash(&d.hash) == rid)
+                    .filter(|d| !d.flag.eq_ignore_ascii_case(&default));
+                if let Some(depend) = depend {
+                    depend_flags.insert(rid.clone(), depend.flag.clone().into());
+                }
+            }
+
+            j.videos.insert(lang, rid.into());
+        }
+
+        if !depend_flags.is_empty() {
+            j.depend_flags = Some(depend_flags);
         }
 
         for (lang, subtitle) in std::iter::zip(data.subtitle_languages, data.subtitles) {
             j.subtitles.insert(lang, subtitle.into());
         }
 
-        let meta: ResourceMeta = serde_json::from_str(&meta_json)?;
         j.hash = meta.hash_path.unwrap_or(meta.hash_value);
 
         Ok(j)
     }
 
+    pub fn normalize(&self, json: &str) -> LangResult<String> {
+        normalize::<RtlvJson>(json)
+    }
+
     pub fn rebuild(&mut self, json: String) -> LangResult<Rebuilt> {
+        self.rebuild_with(json, None)
+    }
+
+    // Rebuilds keeping the relocation table of an existing RTLV, for files with
+    // relocations beyond the pointers that rebuild writes. The table is only kept
+    // if it still has every pointer, so adding or removing languages, or a string
+    // length change that moves a later vector, falls back to the normal table. A
+    // reference that can't be read is treated the same way.
+    pub fn rebuild_like(&mut self, json: String, reference: &[u8]) -> LangResult<Rebuilt> {
+        self.rebuild_with(json, Some(reference))
+    }
+
+    fn rebuild_with(&mut self, json: String, reference: Option<&[u8]>) -> LangResult<Rebuilt> {
         self.depends.clear();
 
-        let json: RtlvJson = serde_json::from_str(&json)?;
+        let json: RtlvJson = parse_json(&json)?;
 
         if json.videos.is_empty() {
             return Err(LangError::InvalidInput);
         }
 
+        // The langmap property overrides the struct's language map, same as DLGE.
+        let lang_map: Vec<String> = match &json.langmap {
+            Some(langmap) => langmap.split(',').map(|s| s.to_string()).collect(),
+            None => self.lang_map.clone(),
+        };
+
         let mut rtlv = GameRtlv {
             video_languages: Vec::new(),
             video_rids: Vec::new(),
@@ -284,21 +465,28 @@ impl RTLV {
         };
 
         for (lang, video) in json.videos {
-            let index = self.lang_map.iter().position(|x| *x == lang).unwrap();
+            let index = lang_map
+                .iter()
+                .position(|x| *x == lang)
+                .ok_or(LangError::InvalidLanguageMap)?;
 
             if let Some(video) = video.as_str() {
                 rtlv.video_languages.push(lang);
-                rtlv.video_rids.push(u64::from_str_radix(
-                    &if !is_valid_hash(video) {
-                        compute_hash(video)
-                    } else {
-                        video.to_string()
-                    },
-                    16,
-                )?);
-
-                self.depends
-                    .insert(video.to_string(), format!("{:2X}", 0x80 + index));
+                let rid = video_rid(video)?;
+                rtlv.video_rids.push(rid);
+
+                // Paths are kept as the depend so they stay readable in the meta,
+                // otherwise the depend is the RID.
+                let depend = if video.starts_with('[') {
+                    video.to_string()
+                } else {
+                    u64_to_hex(rid)
+                };
+                // Flags can be given for the path or the RID.
+                let default = format!("{:2X}", 0x80 + index);
+                let default = depend_flag(&json.depend_flags, &u64_to_hex(rid), default);
+                let flag = depend_flag(&json.depend_flags, &depend, default);
+                self.depends.insert(depend, flag);
             } else {
                 return Err(LangError::InvalidInput);
             }
@@ -307,21 +495,215 @@ impl RTLV {
         for (lang, subtitle) in json.subtitles {
             if let Some(subtitle) = subtitle.as_str() {
                 rtlv.subtitle_languages.push(lang);
-                rtlv.subtitles.push(subtitle.to_string());
+                rtlv.subtitles
+                    .push(self.string_policy.encoded(subtitle).into_owned());
             } else {
                 return Err(LangError::InvalidInput);
             }
         }
 
-        let buf = rtlv.serialize()?;
-        Ok(Rebuilt {
-            file: buf.clone(),
-            meta: serde_json::to_string(&ResourceMeta::new(
-                json.hash,
-                buf.len() as u32,
-                "RTLV".into(),
-                self.depends.clone(),
-            ))?,
-        })
+        let reference = reference.and_then(|data| GameRtlv::read_relocations(data).ok());
+        let buf = rtlv.serialize(reference.as_deref())?;
+        let meta = ResourceMeta::new(
+            json.hash,
+            buf.len() as u32,
+            "RTLV".into(),
+            self.depends.clone(),
+        );
+        check_min_size(buf.len(), MIN_SIZE)?;
+
+        Rebuilt::new(buf, &meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "$schema": "",
+        "hash": "00123456789ABCDE",
+        "videos": {"en": "00FEDCBA98765432"},
+        "subtitles": {"en": "Hello"}
+    }"#;
+
+    // Adds a relocation to the end of a rebuilt file's table.
+    fn with_extra_relocation(file: &[u8], relocation: u32) -> Vec<u8> {
+        let table = 0x10 + u32::from_be_bytes(file[0x08..0x0C].try_into().unwrap()) as usize;
+        let mut file = file.to_vec();
+        for (pos, add) in [(table + 4, 4), (table + 8, 1)] {
+            let value = u32::from_le_bytes(file[pos..pos + 4].try_into().unwrap()) + add;
+            file[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        file.extend(relocation.to_le_bytes());
+        file
+    }
+
+    fn rtlv() -> RTLV {
+        RTLV::new(Version::H3, None).unwrap()
+    }
+
+    #[test]
+    fn rebuild_like_keeps_extra_relocations() {
+        let plain = rtlv().rebuild(JSON.into()).unwrap().file;
+        let reference = with_extra_relocation(&plain, 0x04);
+
+        let mut relocations = GameRtlv::read_relocations(&reference).unwrap();
+        assert_eq!(relocations.pop(), Some(0x04));
+        assert_eq!(relocations, GameRtlv::read_relocations(&plain).unwrap());
+
+        assert_eq!(
+            rtlv().rebuild_like(JSON.into(), &reference).unwrap().file,
+            reference
+        );
+        assert_eq!(rtlv().rebuild(JSON.into()).unwrap().file, plain);
+    }
+
+    #[test]
+    fn rebuild_like_falls_back_when_the_layout_changes() {
+        let plain = rtlv().rebuild(JSON.into()).unwrap().file;
+        let reference = with_extra_relocation(&plain, 0x04);
+
+        // A second subtitle adds a string pointer the reference doesn't have.
+        let json = JSON.replace(r#""Hello""#, r#""Hello", "fr": "Bonjour""#);
+        let rebuilt = rtlv().rebuild_like(json.clone(), &reference).unwrap().file;
+        assert_eq!(rebuilt, rtlv().rebuild(json).unwrap().file);
+
+        // A reference that isn't an RTLV at all is ignored.
+        let rebuilt = rtlv()
+            .rebuild_like(JSON.into(), b"not an rtlv")
+            .unwrap()
+            .file;
+        assert_eq!(rebuilt, plain);
+    }
+
+    #[test]
+    fn custom_langmap_round_trip() {
+        // en is first, so its video gets flag 80 rather than the default map's 81.
+        let lang_map = vec!["en".to_string(), "fr".to_string()];
+        let mut custom = RTLV::new(Version::H3, Some(lang_map)).unwrap();
+        let rebuilt = custom.rebuild(JSON.into()).unwrap();
+
+        let json = custom.convert(&rebuilt.file, rebuilt.meta.clone()).unwrap();
+        assert_eq!(json.langmap.as_deref(), Some("en,fr"));
+        let meta = ResourceMeta::from_json(&rebuilt.meta).unwrap();
+        assert_eq!(meta.hash_reference_data[0].flag, "80");
+
+        // The langmap in the JSON is used over the converter's own map.
+        let json = serde_json::to_string(&json).unwrap();
+        let mut plain = rtlv();
+        assert_eq!(plain.rebuild(json).unwrap().meta, rebuilt.meta);
+        assert_eq!(plain.lang_map, default_lang_map(Version::H3).unwrap());
+    }
+
+    #[test]
+    fn asymmetric_languages_round_trip() {
+        let json = r#"{
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "videos": {"fr": "00FEDCBA98765433", "en": "00FEDCBA98765432"},
+            "subtitles": {"de": "", "fr": "Bonjour"}
+        }"#;
+
+        let mut rtlv = rtlv();
+        let rebuilt = rtlv.rebuild(json.into()).unwrap();
+        let converted = rtlv.convert(&rebuilt.file, rebuilt.meta).unwrap();
+
+        let videos: Vec<_> = converted.videos.keys().collect();
+        let subtitles: Vec<_> = converted.subtitles.iter().collect();
+        assert_eq!(videos, ["fr", "en"]);
+        assert_eq!(
+            subtitles,
+            [
+                (&"de".to_string(), &"".into()),
+                (&"fr".to_string(), &"Bonjour".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn video_paths_and_rids() {
+        let path = "[assembly:/_pro/videos/intro.pc_binkvid].pc_binkvid";
+        let json = serde_json::json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "videos": {"en": path, "fr": "00fedcba98765432", "de": "FEDCBA98765432"},
+            "subtitles": {}
+        });
+
+        let mut rtlv = rtlv();
+        let rebuilt = rtlv.rebuild(json.to_string()).unwrap();
+        let converted = rtlv.convert(&rebuilt.file, rebuilt.meta.clone()).unwrap();
+
+        // Paths and anything that isn't exactly 16 hex digits are hashed.
+        assert_eq!(converted.videos["en"], compute_hash(path));
+        assert_eq!(converted.videos["fr"], "00FEDCBA98765432");
+        assert_eq!(converted.videos["de"], compute_hash("FEDCBA98765432"));
+
+        let meta = ResourceMeta::from_json(&rebuilt.meta).unwrap();
+        let depends: Vec<_> = meta.hash_reference_data.iter().map(|d| &d.hash).collect();
+        assert_eq!(
+            depends,
+            [path, "00FEDCBA98765432", &compute_hash("FEDCBA98765432")]
+        );
+    }
+
+    #[test]
+    fn video_depend_flags_round_trip() {
+        let path = "[assembly:/_pro/videos/intro.pc_binkvid].pc_binkvid";
+        let json = serde_json::json!({
+            "$schema": "",
+            "hash": "00123456789ABCDE",
+            "videos": {"en": path, "fr": "00FEDCBA98765432"},
+            "subtitles": {},
+            "dependFlags": {path: "1F", "00FEDCBA98765432": "9F"}
+        });
+
+        let mut rtlv = rtlv();
+        let rebuilt = rtlv.rebuild(json.to_string()).unwrap();
+        let converted = rtlv.convert(&rebuilt.file, rebuilt.meta).unwrap();
+
+        // The path-form video's flag is recorded by the RID it converts to.
+        let flags = converted.depend_flags.clone().unwrap();
+        assert_eq!(flags[&compute_hash(path)], "1F");
+        assert_eq!(flags["00FEDCBA98765432"], "9F");
+
+        let json = serde_json::to_string(&converted).unwrap();
+        let meta = ResourceMeta::from_json(&rtlv.rebuild(json).unwrap().meta).unwrap();
+        let depends: Vec<_> = meta
+            .hash_reference_data
+            .iter()
+            .map(|d| (d.hash.as_str(), d.flag.as_str()))
+            .collect();
+        assert_eq!(
+            depends,
+            [
+                (compute_hash(path).as_str(), "1F"),
+                ("00FEDCBA98765432", "9F")
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_vectors_are_an_error() {
+        let Rebuilt { file, meta } = rtlv().rebuild(JSON.into()).unwrap();
+
+        // An end pointer before the start, for the video languages and the RIDs.
+        for end in [0x18, 0x30] {
+            let mut file = file.clone();
+            file[end..end + 8].fill(0);
+            assert!(matches!(
+                rtlv().convert(&file, meta.clone()),
+                Err(LangError::InvalidInput)
+            ));
+        }
+
+        // Too short for the BIN1 header.
+        let mut meta: serde_json::Value = serde_json::from_str(&meta).unwrap();
+        meta["hash_size_final"] = 0x0C.into();
+        assert!(matches!(
+            rtlv().convert(&file[..0x0C], meta.to_string()),
+            Err(LangError::InvalidInput)
+        ));
     }
 }
@@ -0,0 +1,85 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::glob;
+
+use super::{
+    dlge::{WeightFormat, DLGE},
+    hashlist::HashList,
+    locr::LOCR,
+};
+use crate::Version;
+
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub file: PathBuf,
+    pub language: String,
+    // The line hash for LOCR, the wav name for DLGE.
+    pub key: String,
+    pub text: String,
+}
+
+fn read_input(path: &Path) -> Option<(Vec<u8>, String)> {
+    let data = fs::read(path).ok()?;
+    let meta = fs::read_to_string(format!("{}.meta.JSON", path.to_str()?)).ok()?;
+    Some((data, meta))
+}
+
+// Searches every DLGE and LOCR (with a .meta.JSON next to it) under the folder for
+// subtitles containing the query. Matching is case-insensitive, files that fail
+// to convert are skipped.
+pub fn search_subtitles(
+    folder: &Path,
+    version: Version,
+    hashlist: &HashList,
+    query: &str,
+) -> Vec<Match> {
+    let query = query.to_lowercase();
+    let mut matches = Vec::new();
+
+    let Ok(dlge) = DLGE::new(hashlist.clone(), version, None, None, WeightFormat::default()) else {
+        return matches;
+    };
+    let Ok(locr) = LOCR::new(hashlist.clone(), version, None, false) else {
+        return matches;
+    };
+
+    for ext in ["DLGE", "LOCR"] {
+        let pattern = folder.join("**").join(format!("*.{}", ext));
+        let Some(pattern) = pattern.to_str() else {
+            continue;
+        };
+        let Ok(entries) = glob(pattern) else {
+            continue;
+        };
+
+        for path in entries.flatten() {
+            let Some((data, meta)) = read_input(&path) else {
+                continue;
+            };
+
+            let lines = match ext {
+                "DLGE" => dlge.convert(&data, meta).map(|j| j.flatten()),
+                _ => locr.convert(&data, meta).map(|j| j.flatten()),
+            };
+            let Ok(lines) = lines else {
+                continue;
+            };
+
+            for (key, language, text) in lines {
+                if text.to_lowercase().contains(&query) {
+                    matches.push(Match {
+                        file: path.clone(),
+                        language,
+                        key,
+                        text,
+                    });
+                }
+            }
+        }
+    }
+
+    matches
+}
@@ -1,8 +1,18 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use clap::{Parser, Subcommand, ValueEnum};
-use glob::glob;
-use tonytools::{hashlist::HashList, hmlanguages, Version};
+use glob::{glob, Pattern};
+use log::{error, info, warn, LevelFilter};
+use serde::Deserialize;
+use tonytools::{
+    dlge::WeightFormat, hashlist::HashList, hmlanguages, hmlanguages::Filetype,
+    rpkg::ResourceMeta, Version,
+};
 
 #[derive(ValueEnum, Clone, Debug)]
 enum GameVersion {
@@ -11,13 +21,31 @@ enum GameVersion {
     H2016,
 }
 
+impl From<GameVersion> for Version {
+    fn from(version: GameVersion) -> Self {
+        match version {
+            GameVersion::H3 => Version::H3,
+            GameVersion::H2 => Version::H2,
+            GameVersion::H2016 => Version::H2016,
+        }
+    }
+}
+
 #[derive(ValueEnum, Clone, Debug)]
-enum Filetype {
-    CLNG,
-    DLGE,
-    DITL,
-    LOCR,
-    RTLV,
+enum Weights {
+    Float,
+    Hex,
+    Raw,
+}
+
+impl From<Weights> for WeightFormat {
+    fn from(weights: Weights) -> Self {
+        match weights {
+            Weights::Float => WeightFormat::Float,
+            Weights::Hex => WeightFormat::Hex,
+            Weights::Raw => WeightFormat::Raw,
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -25,6 +53,18 @@ struct Args {
     #[arg(value_enum)]
     version: GameVersion,
 
+    /// More output per use, -v for debug and -vv for trace. RUST_LOG overrides both.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only errors.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Prints timing and throughput for each file, and totals for batches.
+    #[arg(long, global = true)]
+    stats: bool,
+
     #[arg(value_enum)]
     file_type: Filetype,
 
@@ -53,8 +93,8 @@ enum Commands {
         symmetric: bool,
 
         #[clap(long)]
-        #[clap(default_value_t = false)]
-        hex_precision: bool,
+        #[clap(value_enum, default_value_t = Weights::Float)]
+        weight_format: Weights,
     },
     Rebuild {
         input: PathBuf,
@@ -73,6 +113,42 @@ enum Commands {
         #[clap(long)]
         #[clap(default_value_t = false)]
         symmetric: bool,
+
+        /// How integer DLGE weights are read, raw reads them as the stored value
+        #[clap(long)]
+        #[clap(value_enum, default_value_t = Weights::Float)]
+        weight_format: Weights,
+    },
+    /// Prints the meta and a summary of the file without converting it.
+    Info {
+        input: PathBuf,
+
+        #[clap(long)]
+        meta_path: Option<PathBuf>,
+
+        #[clap(long)]
+        lang_map: Option<String>,
+
+        #[clap(long)]
+        #[clap(default_value_t = false)]
+        symmetric: bool,
+    },
+    /// Converts then rebuilds the file and compares the result with the original.
+    Verify {
+        input: PathBuf,
+
+        #[clap(long)]
+        meta_path: Option<PathBuf>,
+
+        #[clap(long)]
+        lang_map: Option<String>,
+
+        #[clap(long)]
+        default_locale: Option<String>,
+
+        #[clap(long)]
+        #[clap(default_value_t = false)]
+        symmetric: bool,
     },
     Batch {
         #[command(subcommand)]
@@ -101,9 +177,22 @@ enum BatchCommands {
         #[clap(default_value_t = false)]
         symmetric: bool,
 
+        #[clap(long)]
+        #[clap(value_enum, default_value_t = Weights::Float)]
+        weight_format: Weights,
+
         #[clap(long)]
         #[clap(default_value_t = false)]
-        hex_precision: bool,
+        fail_fast: bool,
+
+        #[clap(long)]
+        #[clap(default_value_t = false)]
+        skip_existing: bool,
+
+        /// JSON rules overriding the version and lang map per file, first match wins,
+        /// unmatched files use CLI options
+        #[clap(long)]
+        manifest: Option<PathBuf>,
     },
     Rebuild {
         input_folder: PathBuf,
@@ -123,9 +212,176 @@ enum BatchCommands {
         #[clap(long)]
         #[clap(default_value_t = false)]
         symmetric: bool,
+
+        /// How integer DLGE weights are read, raw reads them as the stored value
+        #[clap(long)]
+        #[clap(value_enum, default_value_t = Weights::Float)]
+        weight_format: Weights,
+
+        #[clap(long)]
+        #[clap(default_value_t = false)]
+        fail_fast: bool,
+
+        // Deployed names come from the rebuilt hash, so they can't be checked up front.
+        #[clap(long, conflicts_with = "chunk")]
+        #[clap(default_value_t = false)]
+        skip_existing: bool,
+
+        #[clap(long)]
+        chunk: Option<String>,
+
+        /// JSON rules overriding the version and lang map per file, first match wins,
+        /// unmatched files use CLI options
+        #[clap(long)]
+        manifest: Option<PathBuf>,
     },
 }
 
+// Per-file overrides for batch jobs with mixed content, from a JSON array like
+// [{"pattern": "*_h2.LOCR", "version": "h2", "lang_map": "xx,en,fr"}]. Patterns are
+// globs matched against the input's file name, so rebuild manifests match the
+// .json names. Rules are checked in order and the first match wins. Files that
+// match nothing use the command line options, as do any options a rule leaves out.
+#[derive(Deserialize)]
+struct ManifestRule {
+    pattern: String,
+    version: Option<String>,
+    lang_map: Option<String>,
+}
+
+struct Manifest {
+    rules: Vec<(Pattern, Option<Version>, Option<Vec<String>>)>,
+    converters: Vec<Option<Converter>>,
+}
+
+impl Manifest {
+    fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("{:?}", e))?;
+        let rules: Vec<ManifestRule> = serde_json::from_str(&json).map_err(|e| format!("{:?}", e))?;
+
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let pattern = Pattern::new(&rule.pattern).map_err(|e| format!("{:?}", e))?;
+                let version = match rule.version {
+                    Some(version) => Some(GameVersion::from_str(&version, true)?.into()),
+                    None => None,
+                };
+                let lang_map = rule.lang_map.map(|map| map.split(',').map(|s| s.to_string()).collect());
+
+                Ok((pattern, version, lang_map))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Manifest {
+            converters: rules.iter().map(|_| None).collect(),
+            rules,
+        })
+    }
+
+    // The converter for the first rule matching the file, made on first use.
+    fn converter(
+        &mut self,
+        file_name: &str,
+        make: impl FnOnce(Option<Version>, Option<Vec<String>>) -> Converter,
+    ) -> Option<&mut Converter> {
+        let index = self.rules.iter().position(|(pattern, ..)| pattern.matches(file_name))?;
+        let (_, version, lang_map) = &self.rules[index];
+
+        Some(self.converters[index].get_or_insert_with(|| make(*version, lang_map.clone())))
+    }
+}
+
+// Counts a failed file in a batch, stopping the batch early if fail fast is set.
+macro_rules! fail_file {
+    ($failures:ident, $fail_fast:ident) => {{
+        $failures += 1;
+        if $fail_fast {
+            break;
+        }
+        continue;
+    }};
+}
+
+// Batch outputs count as done if they were written after their input. Converted
+// JSON is only moved into place once it's fully written, and a rebuild needs its
+// meta as well, so a run that failed partway never leaves an output that passes.
+fn is_up_to_date(input: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+
+    matches!((modified(input), modified(output)), (Some(input), Some(output)) if output >= input)
+}
+
+// Writes through a temporary file next to the output and only renames it into
+// place once everything is written, so a failed conversion never leaves a
+// truncated output behind.
+fn write_atomic<E: From<io::Error>>(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<fs::File>) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let result = fs::File::create(&tmp).map_err(E::from).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        Ok(writer.flush()?)
+    });
+
+    let result = result.and_then(|_| Ok(fs::rename(&tmp, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    result
+}
+
+// Each file is timed from reading its input to writing its output, and its
+// bytes are the input's size.
+#[derive(Default)]
+struct Stats {
+    files: usize,
+    bytes: usize,
+    slowest: Option<(String, Duration)>,
+}
+
+impl Stats {
+    fn record(&mut self, name: &str, bytes: usize, lines: Option<usize>, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let mut line = format!(
+            "{}: {} bytes in {:.2?} ({:.2} MB/s)",
+            name,
+            bytes,
+            elapsed,
+            bytes as f64 / secs / 1_000_000.0
+        );
+        if let Some(lines) = lines {
+            line += &format!(", {} lines ({:.0} lines/s)", lines, lines as f64 / secs);
+        }
+        println!("{}", line);
+
+        self.files += 1;
+        self.bytes += bytes;
+        if !self.slowest.as_ref().is_some_and(|(_, slowest)| *slowest >= elapsed) {
+            self.slowest = Some((name.to_string(), elapsed));
+        }
+    }
+
+    fn summary(&self, elapsed: Duration) {
+        println!(
+            "Total: {} files, {} bytes in {:.2?} ({:.2} MB/s)",
+            self.files,
+            self.bytes,
+            elapsed,
+            self.bytes as f64 / elapsed.as_secs_f64().max(f64::EPSILON) / 1_000_000.0
+        );
+        if let Some((name, elapsed)) = &self.slowest {
+            println!("Slowest: {} ({:.2?})", name, elapsed);
+        }
+    }
+}
+
 enum Converter {
     CLNG(hmlanguages::clng::CLNG),
     DITL(hmlanguages::ditl::DITL),
@@ -141,7 +397,7 @@ impl Converter {
         version: Version,
         lang_map: Option<Vec<String>>,
         default_locale: Option<String>,
-        hex_precision: bool,
+        weight_format: WeightFormat,
         symmetric: bool,
     ) -> Self {
         match file_type {
@@ -156,7 +412,7 @@ impl Converter {
                 Converter::DITL(converter)
             }
             Filetype::DLGE => {
-                let converter = hmlanguages::dlge::DLGE::new(hashlist, version, lang_map, default_locale, hex_precision)
+                let converter = hmlanguages::dlge::DLGE::new(hashlist, version, lang_map, default_locale, weight_format)
                     .expect("Failed to get converter for DLGE.");
                 Converter::DLGE(converter)
             }
@@ -172,6 +428,34 @@ impl Converter {
             }
         }
     }
+
+    fn convert(&self, data: &[u8], meta_json: String) -> hmlanguages::LangResult<String> {
+        Ok(match self {
+            Converter::CLNG(clng) => serde_json::to_string(&clng.convert(data, meta_json)?)?,
+            Converter::DITL(ditl) => serde_json::to_string(&ditl.convert(data, meta_json)?)?,
+            Converter::DLGE(dlge) => serde_json::to_string(&dlge.convert(data, meta_json)?)?,
+            Converter::LOCR(locr) => serde_json::to_string(&locr.convert(data, meta_json)?)?,
+            Converter::RTLV(rtlv) => serde_json::to_string(&rtlv.convert(data, meta_json)?)?,
+        })
+    }
+
+    fn rebuild(&mut self, json: String) -> hmlanguages::LangResult<hmlanguages::Rebuilt> {
+        match self {
+            Converter::CLNG(clng) => clng.rebuild(json),
+            Converter::DITL(ditl) => ditl.rebuild(json),
+            Converter::DLGE(dlge) => dlge.rebuild(json),
+            Converter::LOCR(locr) => locr.rebuild(json),
+            Converter::RTLV(rtlv) => rtlv.rebuild(json),
+        }
+    }
+
+    // Only LOCRs have a line count worth reporting.
+    fn line_count(&self, data: &[u8]) -> Option<usize> {
+        match self {
+            Converter::LOCR(locr) => locr.line_count(data).ok(),
+            _ => None,
+        }
+    }
 }
 
 fn main() {
@@ -182,22 +466,42 @@ fn main() {
 fn real_main() -> i32 {
     let args = Args::parse();
 
-    let version = match args.version {
-        GameVersion::H3 => Version::H3,
-        GameVersion::H2 => Version::H2,
-        GameVersion::H2016 => Version::H2016,
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
     };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let version: Version = args.version.into();
 
     let mut hashlist_path = std::env::current_exe().expect("Failed to get current exe path.");
     hashlist_path.pop();
     hashlist_path.push("hash_list.hmla");
 
-    let hashlist_data = fs::read(hashlist_path);
-    if hashlist_data.is_err() {
-        println!("Hash list not found!");
-        return 1;
-    }
-    let hashlist = HashList::load(&hashlist_data.unwrap()).expect("Failed to load hash list.");
+    let hashlist = match fs::read(hashlist_path) {
+        Ok(data) => HashList::load(&data).expect("Failed to load hash list."),
+        Err(_) => {
+            #[cfg(feature = "embedded-hashlist")]
+            let embedded = HashList::embedded();
+            #[cfg(not(feature = "embedded-hashlist"))]
+            let embedded = None;
+
+            match embedded {
+                Some(hashlist) => hashlist,
+                None => {
+                    error!("Hash list not found!");
+                    return 1;
+                }
+            }
+        }
+    };
 
     match args.cmd {
         Commands::Convert {
@@ -205,24 +509,24 @@ fn real_main() -> i32 {
             output,
             mut meta_path,
             lang_map,
-            hex_precision,
+            weight_format,
             default_locale,
             symmetric,
         } => {
             if !input.exists() {
-                println!("Input path is invalid.");
+                error!("Input path is invalid.");
                 return 1;
             }
 
             if !meta_path.as_ref().is_some_and(|path| path.exists()) {
-                println!("Meta path does not exist. Trying input + .meta.JSON");
+                warn!("Meta path does not exist. Trying input + .meta.JSON");
                 meta_path = Some(PathBuf::from(format!(
                     "{}.meta.JSON",
                     input.to_str().unwrap()
                 )));
 
                 if !meta_path.as_ref().unwrap().exists() {
-                    println!("Could not find meta!");
+                    error!("Could not find meta!");
                     return 1;
                 }
             }
@@ -232,6 +536,11 @@ fn real_main() -> i32 {
             let meta_json =
                 fs::read_to_string(meta_path.unwrap()).expect("Failed to read meta file.");
 
+            let name = input.display().to_string();
+            let size = fs::metadata(&input).map_or(0, |meta| meta.len() as usize);
+            let mut lines = None;
+            let start = Instant::now();
+
             match args.file_type {
                 Filetype::CLNG => {
                     let clng = hmlanguages::clng::CLNG::new(version, lang_map_vec)
@@ -248,7 +557,7 @@ fn real_main() -> i32 {
                         fs::write(output, serde_json::to_string(&clng).unwrap())
                             .expect("Failed to write converted JSON.");
                     } else {
-                        println!("Failed to parse CLNG file {:?}.", json.unwrap_err());
+                        error!("Failed to parse CLNG file {:?}.", json.unwrap_err());
                         return 1;
                     }
                 }
@@ -267,7 +576,7 @@ fn real_main() -> i32 {
                         fs::write(output, serde_json::to_string(&ditl).unwrap())
                             .expect("Failed to write converted JSON.");
                     } else {
-                        println!("Failed to parse DITL file {:?}.", json.unwrap_err());
+                        error!("Failed to parse DITL file {:?}.", json.unwrap_err());
                         return 1;
                     }
                 }
@@ -277,7 +586,7 @@ fn real_main() -> i32 {
                         version,
                         lang_map_vec,
                         default_locale,
-                        hex_precision,
+                        weight_format.into(),
                     )
                     .expect("Failed to get converter for DLGE.");
 
@@ -292,7 +601,7 @@ fn real_main() -> i32 {
                         fs::write(output, serde_json::to_string(&dlge).unwrap())
                             .expect("Failed to write converted JSON.");
                     } else {
-                        println!("Failed to parse DLGE file: {:?}.", json.unwrap_err());
+                        error!("Failed to parse DLGE file: {:?}.", json.unwrap_err());
                         return 1;
                     }
                 }
@@ -300,20 +609,19 @@ fn real_main() -> i32 {
                     let locr = hmlanguages::locr::LOCR::new(hashlist, version, lang_map_vec, symmetric)
                         .expect("Failed to get converter for LOCR.");
 
-                    let json = locr.convert(
-                        fs::read(input)
-                            .expect("Failed to read input file.")
-                            .as_slice(),
-                        meta_json,
-                    );
+                    let data = fs::read(input).expect("Failed to read input file.");
+                    let json = write_atomic(&output, |writer| {
+                        locr.convert_to_writer(&data, meta_json, writer)
+                    });
 
-                    if let Ok(locr) = json {
-                        fs::write(output, serde_json::to_string(&locr).unwrap())
-                            .expect("Failed to write converted JSON.");
-                    } else {
-                        println!("Failed to parse LOCR file {:?}.", json.unwrap_err());
+                    if let Err(err) = json {
+                        error!("Failed to parse LOCR file {:?}.", err);
                         return 1;
                     }
+
+                    if args.stats {
+                        lines = locr.line_count(&data).ok();
+                    }
                 }
                 Filetype::RTLV => {
                     let rtlv = hmlanguages::rtlv::RTLV::new(version, lang_map_vec)
@@ -330,13 +638,17 @@ fn real_main() -> i32 {
                         fs::write(output, serde_json::to_string(&rtlv).unwrap())
                             .expect("Failed to write converted JSON.");
                     } else {
-                        println!("Failed to parse RTLV file {:?}.", json.unwrap_err());
+                        error!("Failed to parse RTLV file {:?}.", json.unwrap_err());
                         return 1;
                     }
                 }
             }
 
-            println!("Converted {:?} to JSON!", args.file_type);
+            if args.stats {
+                Stats::default().record(&name, size, lines, start.elapsed());
+            }
+
+            info!("Converted {:?} to JSON!", args.file_type);
         }
         Commands::Rebuild {
             input,
@@ -345,9 +657,10 @@ fn real_main() -> i32 {
             lang_map,
             default_locale,
             symmetric,
+            weight_format,
         } => {
             if !input.exists() {
-                println!("Input path is invalid.");
+                error!("Input path is invalid.");
                 return 1;
             }
 
@@ -359,6 +672,11 @@ fn real_main() -> i32 {
 
             let lang_map_vec: Option<Vec<String>> = lang_map.map(|map| map.split(',').map(|s| s.to_string()).collect());
 
+            let name = input.display().to_string();
+            let size = fs::metadata(&input).map_or(0, |meta| meta.len() as usize);
+            let mut lines = None;
+            let start = Instant::now();
+
             match args.file_type {
                 Filetype::CLNG => {
                     let clng = hmlanguages::clng::CLNG::new(version, lang_map_vec)
@@ -377,7 +695,7 @@ fn real_main() -> i32 {
                         fs::write(out_meta_path, clng.meta)
                             .expect("Failed to write rebuilt meta file.");
                     } else {
-                        println!("Failed to rebuild CLNG file {:?}.", rebuilt.unwrap_err());
+                        error!("Failed to rebuild CLNG file {:?}.", rebuilt.unwrap_err());
                         return 1;
                     }
                 }
@@ -398,7 +716,7 @@ fn real_main() -> i32 {
                         fs::write(out_meta_path, ditl.meta)
                             .expect("Failed to write rebuilt meta file.");
                     } else {
-                        println!("Failed to rebuild DITL file {:?}.", rebuilt.unwrap_err());
+                        error!("Failed to rebuild DITL file {:?}.", rebuilt.unwrap_err());
                         return 1;
                     }
                 }
@@ -408,7 +726,7 @@ fn real_main() -> i32 {
                         version,
                         lang_map_vec,
                         default_locale,
-                        false,
+                        weight_format.into(),
                     )
                     .expect("Failed to get rebuilder for DLGE.");
 
@@ -425,7 +743,7 @@ fn real_main() -> i32 {
                         fs::write(out_meta_path, dlge.meta)
                             .expect("Failed to write rebuilt meta file.");
                     } else {
-                        println!("Failed to rebuild DLGE file {:?}.", rebuilt.unwrap_err());
+                        error!("Failed to rebuild DLGE file {:?}.", rebuilt.unwrap_err());
                         return 1;
                     }
                 }
@@ -440,13 +758,17 @@ fn real_main() -> i32 {
                         .expect("Failed to utf-8 convert input file."),
                     );
 
-                    if let Ok(locr) = rebuilt {
-                        fs::write(output, locr.file.as_slice())
+                    if let Ok(rebuilt) = rebuilt {
+                        if args.stats {
+                            lines = locr.line_count(&rebuilt.file).ok();
+                        }
+
+                        fs::write(output, rebuilt.file.as_slice())
                             .expect("Failed to write rebuilt file.");
-                        fs::write(out_meta_path, locr.meta)
+                        fs::write(out_meta_path, rebuilt.meta)
                             .expect("Failed to write rebuilt meta file.");
                     } else {
-                        println!("Failed to rebuild LOCR file {:?}.", rebuilt.unwrap_err());
+                        error!("Failed to rebuild LOCR file {:?}.", rebuilt.unwrap_err());
                         return 1;
                     }
                 }
@@ -467,13 +789,173 @@ fn real_main() -> i32 {
                         fs::write(out_meta_path, rtlv.meta)
                             .expect("Failed to write rebuilt meta file.");
                     } else {
-                        println!("Failed to rebuild RTLV file {:?}.", rebuilt.unwrap_err());
+                        error!("Failed to rebuild RTLV file {:?}.", rebuilt.unwrap_err());
                         return 1;
                     }
                 }
             }
 
-            println!("Rebuilt JSON to {:?}!", args.file_type);
+            if args.stats {
+                Stats::default().record(&name, size, lines, start.elapsed());
+            }
+
+            info!("Rebuilt JSON to {:?}!", args.file_type);
+        }
+        Commands::Info {
+            input,
+            mut meta_path,
+            lang_map,
+            symmetric,
+        } => {
+            if !input.exists() {
+                error!("Input path is invalid.");
+                return 1;
+            }
+
+            if !meta_path.as_ref().is_some_and(|path| path.exists()) {
+                meta_path = Some(PathBuf::from(format!(
+                    "{}.meta.JSON",
+                    input.to_str().unwrap()
+                )));
+
+                if !meta_path.as_ref().unwrap().exists() {
+                    error!("Could not find meta!");
+                    return 1;
+                }
+            }
+
+            let meta_json =
+                fs::read_to_string(meta_path.unwrap()).expect("Failed to read meta file.");
+            let meta = match ResourceMeta::from_json(&meta_json) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    error!("Failed to parse meta file {:?}.", err);
+                    return 1;
+                }
+            };
+
+            println!("Type: {}", meta.hash_resource_type);
+            println!("Hash: {}", meta.hash_path.as_ref().unwrap_or(&meta.hash_value));
+            println!("Size: {}", meta.hash_size_final);
+            println!("Dependencies: {}", meta.hash_reference_data.len());
+
+            let data = fs::read(input).expect("Failed to read input file.");
+            let lang_map_vec: Option<Vec<String>> = lang_map.map(|map| map.split(',').map(|s| s.to_string()).collect());
+
+            // Only the parts of the file the summary needs are read. A CLNG is just
+            // its language flags, so converting it is already that cheap.
+            let summary = match Converter::new(args.file_type, hashlist, version, lang_map_vec, None, WeightFormat::default(), symmetric) {
+                Converter::CLNG(clng) => clng.convert(&data, meta_json).map(|json| {
+                    vec![format!("Enabled languages: {}", json.enabled_languages().join(","))]
+                }),
+                Converter::DLGE(dlge) => dlge
+                    .root_type(&data)
+                    .map(|root| vec![format!("Root container: {}", root)]),
+                Converter::LOCR(locr) => locr.line_counts(&data).map(|counts| {
+                    counts
+                        .into_iter()
+                        .map(|(lang, count)| format!("{}: {} lines", lang, count))
+                        .collect()
+                }),
+                Converter::DITL(_) | Converter::RTLV(_) => Ok(Vec::new()),
+            };
+
+            match summary {
+                Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                Err(err) => {
+                    error!("Failed to parse {:?} file {:?}.", args.file_type, err);
+                    return 1;
+                }
+            }
+        }
+        Commands::Verify {
+            input,
+            mut meta_path,
+            lang_map,
+            default_locale,
+            symmetric,
+        } => {
+            if !input.exists() {
+                error!("Input path is invalid.");
+                return 1;
+            }
+
+            if !meta_path.as_ref().is_some_and(|path| path.exists()) {
+                meta_path = Some(PathBuf::from(format!(
+                    "{}.meta.JSON",
+                    input.to_str().unwrap()
+                )));
+
+                if !meta_path.as_ref().unwrap().exists() {
+                    error!("Could not find meta!");
+                    return 1;
+                }
+            }
+
+            let lang_map_vec: Option<Vec<String>> = lang_map.map(|map| map.split(',').map(|s| s.to_string()).collect());
+
+            let meta_json =
+                fs::read_to_string(meta_path.unwrap()).expect("Failed to read meta file.");
+            let data = fs::read(input).expect("Failed to read input file.");
+
+            let mut converter = Converter::new(
+                args.file_type,
+                hashlist,
+                version,
+                lang_map_vec,
+                default_locale,
+                WeightFormat::Raw,
+                symmetric,
+            );
+
+            let json = match converter.convert(&data, meta_json) {
+                Ok(json) => json,
+                Err(err) => {
+                    error!("Failed to parse {:?} file {:?}.", args.file_type, err);
+                    return 1;
+                }
+            };
+
+            let rebuilt = match converter.rebuild(json.clone()) {
+                Ok(rebuilt) => rebuilt,
+                Err(err) => {
+                    error!("Failed to rebuild {:?} file {:?}.", args.file_type, err);
+                    return 1;
+                }
+            };
+
+            if rebuilt.file == data {
+                println!("Identical: rebuilt file matches the original.");
+                return 0;
+            }
+
+            let offset = std::iter::zip(&rebuilt.file, &data)
+                .position(|(a, b)| a != b)
+                .unwrap_or(rebuilt.file.len().min(data.len()));
+            println!(
+                "Bytes differ from offset {:#X} (original {} bytes, rebuilt {} bytes).",
+                offset,
+                data.len(),
+                rebuilt.file.len()
+            );
+
+            // Depends can be reordered on rebuild, so the bytes can differ while
+            // the content is the same. Converting the rebuilt file tells us which.
+            let reconverted = converter.convert(&rebuilt.file, rebuilt.meta.clone());
+            let parse = |json: &str| serde_json::from_str::<serde_json::Value>(json).ok();
+            match reconverted {
+                Ok(reconverted) if parse(&reconverted) == parse(&json) => {
+                    println!("Equivalent: rebuilt file converts to the same JSON.");
+                }
+                Ok(_) => {
+                    println!("Different: rebuilt file converts to different JSON.");
+                    return 1;
+                }
+                Err(err) => {
+                    error!("Failed to parse rebuilt {:?} file {:?}.", args.file_type, err);
+                    return 1;
+                }
+            }
         }
         Commands::Batch { batch } => match batch {
             BatchCommands::Convert {
@@ -483,15 +965,18 @@ fn real_main() -> i32 {
                 lang_map,
                 default_locale,
                 symmetric,
-                hex_precision,
+                weight_format,
+                fail_fast,
+                skip_existing,
+                manifest,
             } => {
                 if !input_folder.exists() {
-                    println!("Input folder is invalid.");
+                    error!("Input folder is invalid.");
                     return 1;
                 }
 
                 if !output_folder.exists() && fs::create_dir_all(output_folder.clone()).is_err() {
-                    println!("Failed to create output folder.");
+                    error!("Failed to create output folder.");
                     return 1;
                 }
 
@@ -501,106 +986,159 @@ fn real_main() -> i32 {
                     input_folder.push("**")
                 }
 
-                let ext = match args.file_type {
-                    Filetype::CLNG => "CLNG",
-                    Filetype::DITL => "DITL",
-                    Filetype::DLGE => "DLGE",
-                    Filetype::LOCR => "LOCR",
-                    Filetype::RTLV => "RTLV",
-                };
+                let ext = args.file_type.four_cc();
 
                 input_folder.push(format!("*.{}", ext));
 
-                let converter = Converter::new(
+                let mut manifest = match manifest.as_deref().map(Manifest::load).transpose() {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        error!("Failed to load manifest - \"{}\"", e);
+                        return 1;
+                    }
+                };
+
+                let mut default_converter = Converter::new(
                     args.file_type,
-                    hashlist,
+                    hashlist.clone(),
                     version,
-                    lang_map_vec,
-                    default_locale,
-                    hex_precision,
+                    lang_map_vec.clone(),
+                    default_locale.clone(),
+                    weight_format.clone().into(),
                     symmetric
                 );
 
+                let mut failures = 0;
+                let mut stats = Stats::default();
+                let started = Instant::now();
+
                 for entry in glob(input_folder.to_str().expect("Failed to convert path.")).expect("Failed to read glob pattern") {
                     if let Err(e) = entry {
-                        println!("Invalid path - \"{:?}\"", e);
-                        continue;
+                        error!("Invalid path - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
 
                     let path = entry.unwrap();
 
+                    let file_name = path.file_name().unwrap().to_str().unwrap();
+
+                    let mut output_path = output_folder.clone();
+                    output_path.push(file_name);
+                    output_path.set_extension(format!("{}.json", ext.to_lowercase()));
+
+                    if skip_existing && is_up_to_date(&path, &output_path) {
+                        info!("Skipped {:?}, output is up to date", file_name);
+                        continue;
+                    }
+
+                    let start = Instant::now();
                     let data = fs::read(path.clone());
                     if let Err(e) = data {
-                        println!("Failed to load file - \"{:?}\"", e);
-                        continue;
+                        error!("Failed to load file - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
+                    let data = data.unwrap();
 
                     let meta_json = fs::read_to_string(PathBuf::from(format!("{}.meta.JSON", path.to_str().unwrap())));
                     if let Err(e) = meta_json {
-                        println!("Failed to load meta - \"{:?}\"", e);
-                        continue;
+                        error!("Failed to load meta - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
 
-                    let file_name = path.file_name().unwrap().to_str().unwrap();
-                    
-                    let json = match converter {
+                    let converter = match manifest.as_mut().and_then(|manifest| {
+                        manifest.converter(file_name, |rule_version, rule_lang_map| {
+                            Converter::new(
+                                args.file_type,
+                                hashlist.clone(),
+                                rule_version.unwrap_or(version),
+                                rule_lang_map.or(lang_map_vec.clone()),
+                                default_locale.clone(),
+                                weight_format.clone().into(),
+                                symmetric,
+                            )
+                        })
+                    }) {
+                        Some(converter) => converter,
+                        None => &mut default_converter,
+                    };
+
+                    let json = match *converter {
                         Converter::CLNG(ref converter) => {
-                            let clng = converter.convert(data.unwrap().as_slice(), meta_json.unwrap());
+                            let clng = converter.convert(&data, meta_json.unwrap());
                             if let Err(e) = clng {
-                                println!("Failed to convert file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to convert file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             serde_json::to_string(&clng.unwrap()).expect("Failed to convert JSON to string.")
                         }
                         Converter::DLGE(ref converter) => {
-                            let dlge = converter.convert(data.unwrap().as_slice(), meta_json.unwrap());
+                            let dlge = converter.convert(&data, meta_json.unwrap());
                             if let Err(e) = dlge {
-                                println!("Failed to convert file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to convert file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             serde_json::to_string(&dlge.unwrap()).expect("Failed to convert JSON to string.")
                         }
                         Converter::LOCR(ref converter) => {
-                            let locr = converter.convert(data.unwrap().as_slice(), meta_json.unwrap());
+                            // LOCRs are streamed straight to the file as they can be huge.
+                            let locr = write_atomic(&output_path, |writer| {
+                                converter.convert_to_writer(&data, meta_json.unwrap(), writer)
+                            });
                             if let Err(e) = locr {
-                                println!("Failed to convert file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to convert file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
-                            serde_json::to_string(&locr.unwrap()).expect("Failed to convert JSON to string.")
+                            if args.stats {
+                                stats.record(file_name, data.len(), converter.line_count(&data).ok(), start.elapsed());
+                            }
+
+                            info!("Processed {:?}", file_name);
+                            continue;
                         }
                         Converter::DITL(ref converter) => {
-                            let ditl = converter.convert(data.unwrap().as_slice(), meta_json.unwrap());
+                            let ditl = converter.convert(&data, meta_json.unwrap());
                             if let Err(e) = ditl {
-                                println!("Failed to convert file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to convert file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             serde_json::to_string(&ditl.unwrap()).expect("Failed to convert JSON to string.")
                         }
                         Converter::RTLV(ref converter) => {
-                            let rtlv = converter.convert(data.unwrap().as_slice(), meta_json.unwrap());
+                            let rtlv = converter.convert(&data, meta_json.unwrap());
                             if let Err(e) = rtlv {
-                                println!("Failed to convert file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to convert file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             serde_json::to_string(&rtlv.unwrap()).expect("Failed to convert JSON to string.")
                         }
                     };
 
-                    let mut output_path = output_folder.clone();
-                    output_path.push(file_name);
-                    output_path.set_extension(format!("{}.json", ext.to_lowercase()));
+                    if let Err(e) =
+                        write_atomic(&output_path, |writer| writer.write_all(json.as_bytes()))
+                    {
+                        error!("Failed to write output file - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
+                    }
 
-                    if let Err(e) = fs::write(output_path, json) {
-                        println!("Failed to write output file - \"{:?}\"", e);
-                        continue;
+                    if args.stats {
+                        stats.record(file_name, data.len(), None, start.elapsed());
                     }
 
-                    println!("Processed {:?}", file_name);
+                    info!("Processed {:?}", file_name);
+                }
+
+                if args.stats {
+                    stats.summary(started.elapsed());
+                }
+
+                if failures > 0 {
+                    error!("Failed to process {} file(s).", failures);
+                    return 1;
                 }
             }
             BatchCommands::Rebuild {
@@ -610,14 +1148,19 @@ fn real_main() -> i32 {
                 lang_map,
                 default_locale,
                 symmetric,
+                weight_format,
+                fail_fast,
+                skip_existing,
+                chunk,
+                manifest,
             } => {
                 if !input_folder.exists() {
-                    println!("Input folder is invalid.");
+                    error!("Input folder is invalid.");
                     return 1;
                 }
 
                 if !output_folder.exists() && fs::create_dir_all(output_folder.clone()).is_err() {
-                    println!("Failed to create output folder.");
+                    error!("Failed to create output folder.");
                     return 1;
                 }
 
@@ -627,54 +1170,94 @@ fn real_main() -> i32 {
 
                 let lang_map_vec: Option<Vec<String>> = lang_map.map(|map| map.split(',').map(|s| s.to_string()).collect());
 
-                let ext = match args.file_type {
-                    Filetype::CLNG => "CLNG",
-                    Filetype::DITL => "DITL",
-                    Filetype::DLGE => "DLGE",
-                    Filetype::LOCR => "LOCR",
-                    Filetype::RTLV => "RTLV",
-                };
+                let ext = args.file_type.four_cc();
 
                 input_folder.push(format!("*.{}.json", ext.to_lowercase()));
 
-                let mut converter = Converter::new(
+                let mut manifest = match manifest.as_deref().map(Manifest::load).transpose() {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        error!("Failed to load manifest - \"{}\"", e);
+                        return 1;
+                    }
+                };
+
+                let mut default_converter = Converter::new(
                     args.file_type,
-                    hashlist,
+                    hashlist.clone(),
                     version,
-                    lang_map_vec,
-                    default_locale,
-                    false,
+                    lang_map_vec.clone(),
+                    default_locale.clone(),
+                    weight_format.clone().into(),
                     symmetric
                 );
 
+                let mut failures = 0;
+                let mut stats = Stats::default();
+                let started = Instant::now();
+
                 for entry in glob(input_folder.to_str().expect("Failed to convert path.")).expect("Failed to read glob pattern") {
                     if let Err(e) = entry {
-                        println!("Invalid path - \"{:?}\"", e);
-                        continue;
+                        error!("Invalid path - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
 
                     let path = entry.unwrap();
 
+                    let file_name = path.file_name().unwrap().to_str().unwrap().split(".").collect::<Vec<&str>>()[0];
+
+                    let mut rebuilt_path = output_folder.clone();
+                    rebuilt_path.push(file_name);
+                    rebuilt_path.set_extension(ext);
+
+                    let mut meta_path = rebuilt_path.clone().into_os_string();
+                    meta_path.push(".meta.JSON");
+
+                    if skip_existing
+                        && is_up_to_date(&path, &rebuilt_path)
+                        && is_up_to_date(&path, Path::new(&meta_path))
+                    {
+                        info!("Skipped {:?}.{:?}.json, output is up to date", file_name, ext.to_lowercase());
+                        continue;
+                    }
+
+                    let start = Instant::now();
                     let file = fs::read(path.clone());
                     if let Err(e) = file {
-                        println!("Failed to load file - \"{:?}\"", e);
-                        continue;
+                        error!("Failed to load file - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
 
                     let data = String::from_utf8(file.unwrap());
                     if let Err(e) = data {
-                        println!("Failed to load JSON file - \"{:?}\"", e);
-                        continue;
+                        error!("Failed to load JSON file - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
+                    let size = data.as_ref().map_or(0, |data| data.len());
+
+                    let converter = match manifest.as_mut().and_then(|manifest| {
+                        manifest.converter(path.file_name().unwrap().to_str().unwrap(), |rule_version, rule_lang_map| {
+                            Converter::new(
+                                args.file_type,
+                                hashlist.clone(),
+                                rule_version.unwrap_or(version),
+                                rule_lang_map.or(lang_map_vec.clone()),
+                                default_locale.clone(),
+                                weight_format.clone().into(),
+                                symmetric,
+                            )
+                        })
+                    }) {
+                        Some(converter) => converter,
+                        None => &mut default_converter,
+                    };
 
-                    let file_name = path.file_name().unwrap().to_str().unwrap().split(".").collect::<Vec<&str>>()[0];
-                    
-                    let rebuilt = match converter {
+                    let rebuilt = match *converter {
                         Converter::CLNG(ref converter) => {
                             let clng = converter.rebuild(data.unwrap());
                             if let Err(e) = clng {
-                                println!("Failed to rebuild file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to rebuild file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             clng.unwrap()
@@ -682,8 +1265,8 @@ fn real_main() -> i32 {
                         Converter::DLGE(ref mut converter) => {
                             let dlge = converter.rebuild(data.unwrap());
                             if let Err(e) = dlge {
-                                println!("Failed to rebuild file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to rebuild file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             dlge.unwrap()
@@ -691,8 +1274,8 @@ fn real_main() -> i32 {
                         Converter::LOCR(ref converter) => {
                             let locr = converter.rebuild(data.unwrap());
                             if let Err(e) = locr {
-                                println!("Failed to rebuild file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to rebuild file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             locr.unwrap()
@@ -700,8 +1283,8 @@ fn real_main() -> i32 {
                         Converter::DITL(ref mut converter) => {
                             let ditl = converter.rebuild(data.unwrap());
                             if let Err(e) = ditl {
-                                println!("Failed to rebuild file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to rebuild file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             ditl.unwrap()
@@ -709,33 +1292,40 @@ fn real_main() -> i32 {
                         Converter::RTLV(ref mut converter) => {
                             let rtlv = converter.rebuild(data.unwrap());
                             if let Err(e) = rtlv {
-                                println!("Failed to rebuild file - \"{:?}\"", e);
-                                continue;
+                                error!("Failed to rebuild file - \"{:?}\"", e);
+                                fail_file!(failures, fail_fast);
                             }
 
                             rtlv.unwrap()
                         }
                     };
 
-                    let mut rebuilt_path = output_folder.clone();
-                    rebuilt_path.push(file_name);
-                    rebuilt_path.set_extension(ext);
-
-                    let mut meta_path = output_folder.clone();
-                    meta_path.push(file_name);
-                    meta_path.set_extension(format!("{}.meta.JSON", ext));
-
-                    if let Err(e) = fs::write(rebuilt_path, rebuilt.file) {
-                        println!("Failed to write rebuilt file - \"{:?}\"", e);
-                        continue;
+                    // With a chunk the output is laid out for an RPKG builder, named by hash.
+                    if let Some(chunk) = &chunk {
+                        if let Err(e) = hmlanguages::deploy(&output_folder, chunk, std::slice::from_ref(&rebuilt)) {
+                            error!("Failed to deploy rebuilt file - \"{:?}\"", e);
+                            fail_file!(failures, fail_fast);
+                        }
+                    } else if let Err(e) = rebuilt.write_to(&rebuilt_path) {
+                        error!("Failed to write rebuilt file - \"{:?}\"", e);
+                        fail_file!(failures, fail_fast);
                     }
 
-                    if let Err(e) = fs::write(meta_path, rebuilt.meta) {
-                        println!("Failed to write meta file - \"{:?}\"", e);
-                        continue;
+                    if args.stats {
+                        let name = format!("{}.{}.json", file_name, ext.to_lowercase());
+                        stats.record(&name, size, converter.line_count(&rebuilt.file), start.elapsed());
                     }
 
-                    println!("Processed {:?}.{:?}.json", file_name, ext.to_lowercase());
+                    info!("Processed {:?}.{:?}.json", file_name, ext.to_lowercase());
+                }
+
+                if args.stats {
+                    stats.summary(started.elapsed());
+                }
+
+                if failures > 0 {
+                    error!("Failed to process {} file(s).", failures);
+                    return 1;
                 }
             }
         },
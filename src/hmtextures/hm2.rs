@@ -8,12 +8,16 @@ use crate::{
 };
 
 use super::{
+    check_header_size, read_format, read_mips,
     structs::{Metadata, RawImage},
     Error,
 };
 
+// Everything up to the pixel data.
+const HEADER_SIZE: usize = 0x90;
+
 #[derive(Default, Debug)]
-struct Texture {
+pub struct Texture {
     pub magic: u16,
     pub metadata: Metadata,
     pub file_size: u32,
@@ -34,6 +38,8 @@ impl Texture {
         let mut texture = Texture::default();
         texture.metadata.version = Version::H2;
 
+        check_header_size(data, Version::H2, HEADER_SIZE)?;
+
         if buf.read::<u16>()?.inner() != 1 {
             return Err(Error::InvalidMagic);
         }
@@ -53,12 +59,9 @@ impl Texture {
             [texture.width, texture.height] = [w as u32, h as u32];
         };
 
-        if let Ok(fmt) = buf.read::<u16>()?.inner().try_into() {
-            texture.metadata.format = fmt;
-        };
+        texture.metadata.format = read_format(&mut buf)?;
 
-        // Skip mip count and default mip
-        buf.consume(0x2);
+        (texture.mips_count, texture.default_mip) = read_mips(&mut buf, &mut texture.metadata)?;
 
         if (buf.read::<u32>()?.inner() == 0x4000) && !is_texd {
             let sf = get_scale_factor(texture.width, texture.height);
@@ -77,7 +80,15 @@ impl Texture {
             return Err(Error::AtlasNotSupported);
         }
 
-        texture.pixels = buf.cursor.to_vec();
+        // We only keep the highest quality mip.
+        let pixel_size =
+            get_pixel_size(texture.metadata.format, texture.width, texture.height, 0) as usize;
+        texture.pixels = buf
+            .cursor
+            .get(..pixel_size)
+            .ok_or(Error::InvalidMipSizes)?
+            .to_vec();
+
         Ok(texture)
     }
 }
@@ -86,10 +97,8 @@ impl From<Texture> for RawImage {
     fn from(val: Texture) -> Self {
         RawImage {
             width: val.width,
-            height: val.width,
-            pixels: val.pixels
-                [..get_pixel_size(val.metadata.format, val.width, val.height, 0) as usize]
-                .to_vec(),
+            height: val.height,
+            pixels: val.pixels,
             metadata: val.metadata,
         }
     }
@@ -1,17 +1,20 @@
 #![allow(dead_code)]
-use bitchomp::{ByteReader, Endianness, ChompFlatten};
+use bitchomp::{ByteReader, ChompFlatten, Endianness};
 use std::io::BufRead;
 
 use crate::{
-    hmtextures::Error,
+    hmtextures::{check_header_size, read_format, read_mips, Error},
     util::texture::{get_pixel_size, get_scale_factor},
     Version,
 };
 
 use super::structs::{Metadata, RawImage};
 
+// Everything up to the pixel data.
+const HEADER_SIZE: usize = 0x5B;
+
 #[derive(Default, Debug)]
-struct Texture {
+pub struct Texture {
     pub magic: u16,
     pub metadata: Metadata,
     pub file_size: u32,
@@ -34,6 +37,8 @@ impl Texture {
         let mut texture = Texture::default();
         texture.metadata.version = Version::H2016;
 
+        check_header_size(data, Version::H2016, HEADER_SIZE)?;
+
         if buf.read::<u16>()?.inner() != 1 {
             return Err(Error::InvalidMagic);
         }
@@ -61,12 +66,9 @@ impl Texture {
             texture.height /= sf;
         }
 
-        if let Ok(fmt) = buf.read::<u16>()?.inner().try_into() {
-            texture.metadata.format = fmt;
-        };
+        texture.metadata.format = read_format(&mut buf)?;
 
-        // Skip mip count + default mip
-        buf.consume(0x2);
+        (texture.mips_count, texture.default_mip) = read_mips(&mut buf, &mut texture.metadata)?;
 
         texture.metadata.interpret_as = buf.read()?.inner();
 
@@ -87,7 +89,15 @@ impl Texture {
             return Err(Error::AtlasNotSupported);
         }
 
-        texture.pixels = buf.cursor.to_vec();
+        // We only keep the highest quality mip.
+        let pixel_size =
+            get_pixel_size(texture.metadata.format, texture.width, texture.height, 0) as usize;
+        texture.pixels = buf
+            .cursor
+            .get(..pixel_size)
+            .ok_or(Error::InvalidMipSizes)?
+            .to_vec();
+
         Ok(texture)
     }
 }
@@ -96,10 +106,8 @@ impl From<Texture> for RawImage {
     fn from(val: Texture) -> Self {
         RawImage {
             width: val.width,
-            height: val.width,
-            pixels: val.pixels
-                [..get_pixel_size(val.metadata.format, val.width, val.height, 0) as usize]
-                .to_vec(),
+            height: val.height,
+            pixels: val.pixels,
             metadata: val.metadata,
         }
     }
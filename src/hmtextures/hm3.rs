@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-use bitchomp::{ByteReader, Endianness, ChompFlatten};
+use bitchomp::{ByteReader, ChompFlatten, Endianness};
 use std::io::BufRead;
 
 use crate::{
@@ -8,12 +8,16 @@ use crate::{
 };
 
 use super::{
+    check_header_size, read_format, read_mips,
     structs::{Metadata, RawImage},
     Error,
 };
 
+// Everything up to the pixel data.
+const HEADER_SIZE: usize = 0x98;
+
 #[derive(Default, Debug)]
-struct Texture {
+pub struct Texture {
     pub magic: u16,
     pub metadata: Metadata,
     pub file_size: u32,
@@ -28,12 +32,22 @@ struct Texture {
     pub pixels: Vec<u8>,
 }
 
+// Scale factors are stored as a power of two, with 0 for an unscaled texture.
+fn scale_factor(exponent: u8) -> Result<u32, Error> {
+    match exponent {
+        0 => Ok(0),
+        n => 1_u32.checked_shl(n as u32).ok_or(Error::InvalidDimensions),
+    }
+}
+
 impl Texture {
     pub fn load(data: &[u8], texd: Option<&[u8]>) -> Result<Self, Error> {
         let mut buf = ByteReader::new(data, Endianness::Little);
         let mut texture = Texture::default();
         texture.metadata.version = Version::H3;
 
+        check_header_size(data, Version::H3, HEADER_SIZE)?;
+
         if buf.read::<u16>()?.inner() != 1 {
             return Err(Error::InvalidMagic);
         }
@@ -53,12 +67,9 @@ impl Texture {
             [texture.width, texture.height] = [w as u32, h as u32];
         };
 
-        if let Ok(fmt) = buf.read::<u16>()?.inner().try_into() {
-            texture.metadata.format = fmt;
-        };
+        texture.metadata.format = read_format(&mut buf)?;
 
-        // Skip mip count and default mip
-        buf.consume(0x2);
+        (texture.mips_count, texture.default_mip) = read_mips(&mut buf, &mut texture.metadata)?;
 
         texture.metadata.interpret_as = buf.read()?.inner();
 
@@ -69,6 +80,11 @@ impl Texture {
         let texture_sizes = buf.read_n::<u32>(0xE)?.flatten();
         let compressed_sizes = buf.read_n::<u32>(0xE)?.flatten();
 
+        // A zero sized top mip means the header is corrupt.
+        if texture_sizes[0] == 0 || compressed_sizes[0] == 0 {
+            return Err(Error::InvalidMipSizes);
+        }
+
         if let [a_s, a_o] = buf.read_n::<u32>(2)?.flatten()[..] {
             [texture.atlas_size, texture.atlas_offset] = [a_s, a_o];
         }
@@ -80,14 +96,8 @@ impl Texture {
         // Skip scaling data
         buf.consume(0x01);
 
-        let width_sf = match buf.read::<u8>()?.inner() {
-            0 => 0,
-            n => 2 << (n - 1),
-        };
-        let height_sf = match buf.read::<u8>()?.inner() {
-            0 => 0,
-            n => 2 << (n - 1),
-        };
+        let width_sf = scale_factor(buf.read()?.inner())?;
+        let height_sf = scale_factor(buf.read()?.inner())?;
         let text_mip_count: u8 = buf.read()?.inner();
 
         // Skip padding
@@ -95,19 +105,29 @@ impl Texture {
 
         texture.pixels = buf.cursor.to_vec();
 
+        // Without a TEXD we only have the downscaled copy in the TEXT, whether
+        // or not it's compressed.
+        if texd.is_none() && width_sf != 0 && height_sf != 0 {
+            texture.width /= width_sf;
+            texture.height /= height_sf;
+        }
+
+        let pixel_size =
+            get_pixel_size(texture.metadata.format, texture.width, texture.height, 0) as usize;
+
         // We only return the highest quality texture as the pixels
         texture.pixels = if let Some(texd) = texd {
+            // A mip size that disagrees with the dimensions would otherwise give
+            // a short image that only fails, or goes blank, when it's decoded.
             lz4_flex::block::decompress(
-                &texd[..compressed_sizes[0] as usize],
+                texd.get(..compressed_sizes[0] as usize)
+                    .ok_or(Error::InvalidMipSizes)?,
                 texture_sizes[0] as usize,
-            )
-            .unwrap()
+            )?
+            .get(..pixel_size)
+            .ok_or(Error::InvalidMipSizes)?
+            .to_vec()
         } else if texture_sizes[0] != compressed_sizes[0] {
-            if width_sf != 0 && height_sf != 0 {
-                texture.width /= width_sf;
-                texture.height /= height_sf;
-            }
-
             let text_size = get_total_size(
                 texture.metadata.format,
                 texture.width,
@@ -117,12 +137,17 @@ impl Texture {
 
             // We decompress the entire pixels object here as it's compressed
             // like that.
-            lz4_flex::block::decompress(&texture.pixels, text_size as usize).unwrap()
-                [..get_pixel_size(texture.metadata.format, texture.width, texture.height, 0)
-                    as usize]
+            lz4_flex::block::decompress(&texture.pixels, text_size as usize)?
+                .get(..pixel_size)
+                .ok_or(Error::InvalidMipSizes)?
                 .to_vec()
         } else {
-            texture.pixels[..texture_sizes[0] as usize].to_vec()
+            // The mip sizes describe the full size texture, not the scaled copy.
+            texture
+                .pixels
+                .get(..pixel_size)
+                .ok_or(Error::InvalidMipSizes)?
+                .to_vec()
         };
 
         Ok(texture)
@@ -133,9 +158,142 @@ impl From<Texture> for RawImage {
     fn from(val: Texture) -> Self {
         RawImage {
             width: val.width,
-            height: val.width,
+            height: val.height,
             pixels: val.pixels,
             metadata: val.metadata,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 16x16 R8G8B8A8 TEXT with one mip of the given sizes, followed by pixels.
+    fn text(size: u32, compressed_size: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut text = vec![0_u8; HEADER_SIZE];
+        text[0x00] = 1;
+        text[0x0C..0x0E].copy_from_slice(&16_u16.to_le_bytes());
+        text[0x0E..0x10].copy_from_slice(&16_u16.to_le_bytes());
+        text[0x10] = 0x1C;
+        text[0x12] = 1;
+        text[0x18..0x1C].copy_from_slice(&size.to_le_bytes());
+        text[0x50..0x54].copy_from_slice(&compressed_size.to_le_bytes());
+        text.extend(pixels);
+        text
+    }
+
+    #[test]
+    fn zeroed_mip_sizes_are_an_error() {
+        let data = text(0, 0, &[0xFF; 16 * 16 * 4]);
+        assert!(matches!(
+            Texture::load(&data, None),
+            Err(Error::InvalidMipSizes)
+        ));
+        assert!(matches!(
+            Texture::load(&data, Some(&[])),
+            Err(Error::InvalidMipSizes)
+        ));
+    }
+
+    #[test]
+    fn garbage_compressed_pixels_are_an_error() {
+        let garbage = [0xFF; 100];
+
+        let data = text(16 * 16 * 4, 100, &garbage);
+        assert!(matches!(
+            Texture::load(&data, None),
+            Err(Error::DecompressError(_))
+        ));
+
+        let data = text(16 * 16 * 4, 100, &[]);
+        assert!(matches!(
+            Texture::load(&data, Some(&garbage)),
+            Err(Error::DecompressError(_))
+        ));
+    }
+
+    #[test]
+    fn uncompressed_pixels_load() {
+        let data = text(16 * 16 * 4, 16 * 16 * 4, &[0xFF; 16 * 16 * 4]);
+        let texture = Texture::load(&data, None).unwrap();
+        assert_eq!(texture.pixels, vec![0xFF; 16 * 16 * 4]);
+    }
+
+    #[test]
+    fn scaled_uncompressed_text_uses_the_scaled_size() {
+        // A 32x32 texture with its TEXT copy halved in both directions.
+        let mut data = text(32 * 32 * 4, 32 * 32 * 4, &[0xFF; 16 * 16 * 4]);
+        data[0x0C..0x0E].copy_from_slice(&32_u16.to_le_bytes());
+        data[0x0E..0x10].copy_from_slice(&32_u16.to_le_bytes());
+        data[0x91] = 1;
+        data[0x92] = 1;
+
+        let texture = Texture::load(&data, None).unwrap();
+        assert_eq!((texture.width, texture.height), (16, 16));
+        assert_eq!(texture.pixels.len(), 16 * 16 * 4);
+    }
+
+    #[test]
+    fn too_many_mips_are_an_error() {
+        let mut data = text(16 * 16 * 4, 16 * 16 * 4, &[0xFF; 16 * 16 * 4]);
+        data[0x12] = 20;
+        assert!(matches!(
+            Texture::load(&data, None),
+            Err(Error::InvalidDimensions)
+        ));
+
+        data[0x12] = 0xE;
+        assert_eq!(Texture::load(&data, None).unwrap().mips_count, 0xE);
+    }
+
+    #[test]
+    fn mip_count_and_default_mip_reach_the_metadata() {
+        let mut data = text(16 * 16 * 4, 16 * 16 * 4, &[0xFF; 16 * 16 * 4]);
+        data[0x12] = 3;
+        data[0x13] = 2;
+
+        let texture = Texture::load(&data, None).unwrap();
+        assert_eq!((texture.mips_count, texture.default_mip), (3, 2));
+
+        let image = RawImage::from(texture);
+        assert_eq!(image.metadata.mips_count, 3);
+        assert_eq!(image.metadata.default_mip, 2);
+
+        let mut metadata = image.metadata.serialize();
+        let read = Metadata::deserialize(&mut ByteReader::new(&metadata, Endianness::Little));
+        assert_eq!(read.unwrap().default_mip, 2);
+
+        // Metadata written before the mips were added still reads, as mip 0.
+        metadata.truncate(metadata.len() - 2);
+        let read = Metadata::deserialize(&mut ByteReader::new(&metadata, Endianness::Little));
+        assert_eq!(read.unwrap().default_mip, 0);
+    }
+
+    #[test]
+    fn oversized_scale_factors_are_an_error() {
+        let mut data = text(16 * 16 * 4, 16 * 16 * 4, &[0xFF; 16 * 16 * 4]);
+        data[0x91] = 32;
+        data[0x92] = 32;
+        assert!(matches!(
+            Texture::load(&data, None),
+            Err(Error::InvalidDimensions)
+        ));
+
+        data[0x91] = 31;
+        data[0x92] = 31;
+        assert!(Texture::load(&data, None).is_ok());
+    }
+
+    #[test]
+    fn oversized_dimensions_are_an_error() {
+        // The size of a 65535x65535 R16G16B16A16 mip doesn't fit in a u32.
+        let mut data = text(16 * 16 * 4, 16 * 16 * 4, &[0xFF; 16 * 16 * 4]);
+        data[0x0C..0x10].fill(0xFF);
+        data[0x10] = 0x0A;
+        assert!(matches!(
+            Texture::load(&data, None),
+            Err(Error::InvalidMipSizes)
+        ));
+    }
+}
@@ -1,21 +1,44 @@
-use std::{convert::Infallible, io};
+use std::io;
 
-use bitchomp::ByteReaderError;
+use bitchomp::{ByteReader, ByteReaderError, Endianness};
+use log::warn;
+use strum_macros::Display;
+
+use crate::Version;
 
 pub mod hm2;
 pub mod hm2016;
 pub mod hm3;
+pub mod montage;
 pub mod structs;
 
-#[derive(Debug)]
+use structs::RawImage;
+
+#[derive(Debug, Display)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
+    #[strum(
+        to_string = "header too short for {version:?} texture (need {expected:#X}, got {actual:#X})"
+    )]
+    InvalidInput {
+        version: Version,
+        expected: usize,
+        actual: usize,
+    },
     InvalidMagic,
     InvalidDimensions,
     UnknownType,
     UnknownFormat,
+    UnknownVersion,
     AtlasNotSupported,
+    InvalidMipSizes,
+    // Block compressed data too short for the image.
+    DecodeError,
+    DecompressError(lz4_flex::block::DecompressError),
     ReaderError(ByteReaderError),
     IO(io::Error),
+    #[cfg(feature = "png")]
+    ImageError(image::ImageError),
 }
 
 impl From<std::io::Error> for Error {
@@ -24,13 +47,144 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<lz4_flex::block::DecompressError> for Error {
+    fn from(err: lz4_flex::block::DecompressError) -> Self {
+        Error::DecompressError(err)
+    }
+}
+
 impl From<ByteReaderError> for Error {
     fn from(err: ByteReaderError) -> Self {
         Error::ReaderError(err)
     }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[cfg(feature = "png")]
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Error::ImageError(err)
+    }
+}
+
+pub type TextureResult<T> = Result<T, Error>;
+
+// A texture from any of the games, with everything its TEXT header says.
+#[derive(Debug)]
+pub enum Texture {
+    H2016(hm2016::Texture),
+    H2(hm2::Texture),
+    H3(hm3::Texture),
+}
+
+impl Texture {
+    // Reads a TEXT, or its TEXD if there is one, with the loader for the game.
+    // H3 takes the pixels from the TEXD but still needs the TEXT's header, while
+    // the H2 and H2016 TEXDs are complete textures so the TEXT isn't read at all.
+    // Use detect_version when the game isn't known.
+    pub fn read(version: Version, data: &[u8], texd: Option<&[u8]>) -> TextureResult<Self> {
+        match (version, texd) {
+            (Version::H2016, Some(texd)) => Ok(Texture::H2016(hm2016::Texture::load(texd, true)?)),
+            (Version::H2016, None) => Ok(Texture::H2016(hm2016::Texture::load(data, false)?)),
+            (Version::H2, Some(texd)) => Ok(Texture::H2(hm2::Texture::load(texd, true)?)),
+            (Version::H2, None) => Ok(Texture::H2(hm2::Texture::load(data, false)?)),
+            (Version::H3, texd) => Ok(Texture::H3(hm3::Texture::load(data, texd)?)),
+            (Version::Unknown, _) => Err(Error::UnknownVersion),
+        }
+    }
+
+    /// The top mip of a TEXT as a [`RawImage`], see [`Texture::read`] for how the
+    /// TEXD is used.
+    ///
+    /// ```
+    /// use tonytools::hmtextures::{Format, Texture};
+    /// use tonytools::Version;
+    ///
+    /// // An uncompressed 512x256 RGBA8 H3 TEXT with a single mip.
+    /// let size = 512 * 256 * 4_u32;
+    /// let mut text = vec![0_u8; 0x98];
+    /// text[0x00] = 1; // magic
+    /// text[0x0C..0x0E].copy_from_slice(&512_u16.to_le_bytes()); // width
+    /// text[0x0E..0x10].copy_from_slice(&256_u16.to_le_bytes()); // height
+    /// text[0x10] = 0x1C; // R8G8B8A8
+    /// text[0x12] = 1; // mip count
+    /// text[0x18..0x1C].copy_from_slice(&size.to_le_bytes()); // mip size
+    /// text[0x50..0x54].copy_from_slice(&size.to_le_bytes()); // compressed size
+    /// text.resize(0x98 + size as usize, 0xFF);
+    ///
+    /// let image = Texture::load(Version::H3, &text, None).unwrap();
+    /// assert_eq!((image.width, image.height), (512, 256));
+    /// assert_eq!(image.metadata.format, Format::R8G8B8A8);
+    /// assert_eq!(image.pixels.len(), size as usize);
+    /// ```
+    pub fn load(version: Version, data: &[u8], texd: Option<&[u8]>) -> TextureResult<RawImage> {
+        Ok(Self::read(version, data, texd)?.into())
+    }
+
+    pub fn metadata(&self) -> &structs::Metadata {
+        match self {
+            Texture::H2016(texture) => &texture.metadata,
+            Texture::H2(texture) => &texture.metadata,
+            Texture::H3(texture) => &texture.metadata,
+        }
+    }
+
+    /// The top mip as stored, before any block decoding, for callers with their
+    /// own decoder. H3 pixels have already been decompressed from LZ4.
+    /// [`Texture::format`] says how they're encoded.
+    ///
+    /// ```
+    /// use tonytools::hmtextures::{Format, Texture};
+    /// use tonytools::Version;
+    ///
+    /// // An 8x8 DXT1 H3 TEXT with a single mip, four 8 byte blocks.
+    /// let mut text = vec![0_u8; 0x98];
+    /// text[0x00] = 1; // magic
+    /// text[0x0C..0x0E].copy_from_slice(&8_u16.to_le_bytes()); // width
+    /// text[0x0E..0x10].copy_from_slice(&8_u16.to_le_bytes()); // height
+    /// text[0x10] = 0x49; // DXT1
+    /// text[0x12] = 1; // mip count
+    /// text[0x18..0x1C].copy_from_slice(&32_u32.to_le_bytes()); // mip size
+    /// text[0x50..0x54].copy_from_slice(&32_u32.to_le_bytes()); // compressed size
+    /// text.extend((0..32).map(|i| i as u8));
+    ///
+    /// let texture = Texture::read(Version::H3, &text, None).unwrap();
+    /// assert_eq!(texture.format(), Format::DXT1);
+    /// assert_eq!(texture.raw_payload(), &text[0x98..]);
+    /// ```
+    pub fn raw_payload(&self) -> &[u8] {
+        match self {
+            Texture::H2016(texture) => &texture.pixels,
+            Texture::H2(texture) => &texture.pixels,
+            Texture::H3(texture) => &texture.pixels,
+        }
+    }
+
+    /// The format of [`Texture::raw_payload`], the same as the metadata's.
+    pub fn format(&self) -> Format {
+        self.metadata().format
+    }
+
+    /// The width and height of the top mip.
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Texture::H2016(texture) => (texture.width, texture.height),
+            Texture::H2(texture) => (texture.width, texture.height),
+            Texture::H3(texture) => (texture.width, texture.height),
+        }
+    }
+}
+
+impl From<Texture> for RawImage {
+    fn from(texture: Texture) -> Self {
+        match texture {
+            Texture::H2016(texture) => texture.into(),
+            Texture::H2(texture) => texture.into(),
+            Texture::H3(texture) => texture.into(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Display, Clone, Copy, PartialEq)]
 pub enum Type {
     Colour,
     Normal,
@@ -41,6 +195,19 @@ pub enum Type {
     Unknown,
 }
 
+impl Type {
+    // Every known type, for listing in a UI. The code is `u16::from`.
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::Colour,
+            Self::Normal,
+            Self::Height,
+            Self::CompoundNormal,
+            Self::Billboard,
+        ]
+    }
+}
+
 impl From<Type> for u16 {
     fn from(r#type: Type) -> Self {
         r#type as u16
@@ -50,18 +217,21 @@ impl From<Type> for u16 {
 impl TryFrom<u16> for Type {
     type Error = self::Error;
     fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Ok([
+        [
             Self::Colour,
             Self::Normal,
             Self::Height,
             Self::CompoundNormal,
             Self::Billboard,
             Self::Unknown,
-        ][(value.try_into() as Result<usize, Infallible>).map_err(|_| self::Error::UnknownType)?])
+        ]
+        .get(value as usize)
+        .copied()
+        .ok_or(self::Error::UnknownType)
     }
 }
 
-#[derive(Default, Copy, Clone, Debug, PartialEq)]
+#[derive(Default, Copy, Clone, Debug, Display, PartialEq)]
 pub enum Format {
     #[default]
     Unknown = 0,
@@ -76,6 +246,23 @@ pub enum Format {
     BC7 = 0x5A,  //high res color + full alpha. Used for pretty much everything...
 }
 
+impl Format {
+    // Every known format, for listing in a UI. The code is `u16::from`.
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::R16G16B16A16,
+            Self::R8G8B8A8,
+            Self::R8G8,
+            Self::A8,
+            Self::DXT1,
+            Self::DXT5,
+            Self::BC4,
+            Self::BC5,
+            Self::BC7,
+        ]
+    }
+}
+
 impl From<Format> for u16 {
     fn from(format: Format) -> Self {
         format as u16
@@ -100,6 +287,52 @@ impl TryFrom<u16> for Format {
     }
 }
 
+// Checks the TEXT is at least as long as the header the loader reads, so a short
+// or truncated file fails with the sizes rather than a read error partway in.
+pub(crate) fn check_header_size(
+    data: &[u8],
+    version: Version,
+    expected: usize,
+) -> Result<(), Error> {
+    if data.len() < expected {
+        return Err(Error::InvalidInput {
+            version,
+            expected,
+            actual: data.len(),
+        });
+    }
+
+    Ok(())
+}
+
+// Reads the format code. One we don't know is left as Unknown so the rest of the
+// header can still be read, it only fails once the pixels are needed.
+pub(crate) fn read_format(buf: &mut ByteReader) -> Result<Format, Error> {
+    let format = buf.read::<u16>()?.inner();
+    Ok(format.try_into().unwrap_or_else(|_| {
+        warn!(
+            "Unknown texture format {:#X}, leaving it as Unknown",
+            format
+        );
+        Format::Unknown
+    }))
+}
+
+// Reads the mip count and default mip into the metadata and returns them. The
+// header only has room for 0xE mip sizes.
+pub(crate) fn read_mips(
+    buf: &mut ByteReader,
+    metadata: &mut structs::Metadata,
+) -> Result<(u8, u8), Error> {
+    metadata.mips_count = buf.read()?.inner();
+    if metadata.mips_count > 0xE {
+        return Err(Error::InvalidDimensions);
+    }
+    metadata.default_mip = buf.read()?.inner();
+
+    Ok((metadata.mips_count, metadata.default_mip))
+}
+
 // Cut down version of the one in the image crate.
 #[derive(Copy, Clone)]
 pub enum ColourType {
@@ -108,3 +341,151 @@ pub enum ColourType {
     Rgba8,
     Rgba16,
 }
+
+impl ColourType {
+    pub fn channels(self) -> usize {
+        match self {
+            ColourType::L8 => 1,
+            ColourType::Rgb8 => 3,
+            ColourType::Rgba8 | ColourType::Rgba16 => 4,
+        }
+    }
+
+    // Bits per channel.
+    pub fn bit_depth(self) -> usize {
+        match self {
+            ColourType::Rgba16 => 16,
+            _ => 8,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        self.channels() * self.bit_depth() / 8
+    }
+}
+
+impl TryFrom<u8> for ColourType {
+    type Error = self::Error;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        [Self::L8, Self::Rgb8, Self::Rgba8, Self::Rgba16]
+            .get(value as usize)
+            .copied()
+            .ok_or(self::Error::UnknownFormat)
+    }
+}
+
+/// Guesses the game a TEXT header came from by checking which layout it fits.
+///
+/// H2016 stores the TEXD flag before the file size, so its format lives at 0x14
+/// rather than 0x10. H2 and H3 share the first 0x14 bytes; H2 then has the 0x4000
+/// flag dword where H3 has interpret_as, dimensions and the interpolation mode.
+/// An H3 header with all of those zeroed reads the same as an H2 one without a
+/// TEXD, so in that case we fall back to H3's scaling data after the atlas info,
+/// which is only a guess. An H2 file size of exactly 0 or 0x4000 looks like the
+/// H2016 TEXD flag, but H2016's format would then be H2's 0x4000 flag dword,
+/// which is never a valid format.
+pub fn detect_version(data: &[u8]) -> Option<Version> {
+    let mut buf = ByteReader::new(data, Endianness::Little);
+    let mut read_u16 = |pos: usize| -> Option<u16> {
+        buf.seek(pos).ok()?;
+        Some(buf.read::<u16>().ok()?.inner())
+    };
+
+    if read_u16(0x00)? != 1 || read_u16(0x02)? > 3 {
+        return None;
+    }
+
+    let valid = |w: u16, h: u16, fmt: u16| w != 0 && h != 0 && Format::try_from(fmt).is_ok();
+
+    let h2016 = read_u16(0x06)? == 0
+        && matches!(read_u16(0x04)?, 0 | 0x4000)
+        && valid(read_u16(0x10)?, read_u16(0x12)?, read_u16(0x14)?);
+    if h2016 {
+        return Some(Version::H2016);
+    }
+
+    if !valid(read_u16(0x0C)?, read_u16(0x0E)?, read_u16(0x10)?) {
+        return None;
+    }
+
+    match data.get(0x14..0x18)? {
+        [0x00, 0x40, 0x00, 0x00] => return Some(Version::H2),
+        [0x00, 0x00, 0x00, 0x00] => {}
+        _ => return Some(Version::H3),
+    }
+
+    // Scaling exponents, mip count then zeroed padding.
+    match data.get(0x90..0x98)? {
+        [_, w, h, mips, 0, 0, 0, 0] if *w <= 8 && *h <= 8 && *mips <= 0xE => Some(Version::H3),
+        _ => Some(Version::H2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 16x16 R8G8B8A8 header of the given game, padded with 0xFF.
+    fn header(version: Version, len: usize) -> Vec<u8> {
+        let mut data = vec![0xFF; len];
+        data[0x00..0x04].copy_from_slice(&[1, 0, 0, 0]);
+        let dimensions = match version {
+            Version::H2016 => {
+                data[0x04..0x08].fill(0);
+                0x10
+            }
+            _ => {
+                data[0x04..0x08].copy_from_slice(&0x1234_u32.to_le_bytes());
+                0x0C
+            }
+        };
+        data[dimensions..dimensions + 4].copy_from_slice(&[16, 0, 16, 0]);
+        data[dimensions + 4..dimensions + 6].copy_from_slice(&0x1C_u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn detects_each_game() {
+        assert_eq!(
+            detect_version(&header(Version::H2016, 0x5B)),
+            Some(Version::H2016)
+        );
+
+        let mut h2 = header(Version::H2, 0x90);
+        h2[0x14..0x18].copy_from_slice(&0x4000_u32.to_le_bytes());
+        assert_eq!(detect_version(&h2), Some(Version::H2));
+
+        let mut h3 = header(Version::H3, 0x98);
+        h3[0x14..0x18].copy_from_slice(&[0, 0, 1, 0]);
+        assert_eq!(detect_version(&h3), Some(Version::H3));
+
+        assert_eq!(detect_version(&h3[..0x10]), None);
+        h3[0x00] = 2;
+        assert_eq!(detect_version(&h3), None);
+    }
+
+    #[test]
+    fn zeroed_h3_falls_back_to_the_scaling_data() {
+        let mut h3 = header(Version::H3, 0xA0);
+        h3[0x14..0x18].fill(0);
+        h3[0x90..0x98].copy_from_slice(&[0, 1, 1, 5, 0, 0, 0, 0]);
+        assert_eq!(detect_version(&h3), Some(Version::H3));
+
+        // An H2 TEXT without a TEXD, where the same bytes are its pixels.
+        let mut h2 = header(Version::H2, 0xA0);
+        h2[0x14..0x18].fill(0);
+        assert_eq!(detect_version(&h2), Some(Version::H2));
+    }
+
+    #[test]
+    fn h2_file_sizes_that_look_like_the_texd_flag() {
+        for size in [0_u32, 0x4000] {
+            for flag in [0_u32, 0x4000] {
+                let mut h2 = header(Version::H2, 0xA0);
+                h2[0x04..0x08].copy_from_slice(&size.to_le_bytes());
+                h2[0x14..0x18].copy_from_slice(&flag.to_le_bytes());
+                assert_eq!(detect_version(&h2), Some(Version::H2));
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use super::{
+    structs::{Metadata, RawImage},
+    Error, Format, TextureResult,
+};
+
+// Space around each thumbnail and its caption, in pixels.
+const PADDING: u32 = 4;
+const BACKGROUND: [u8; 3] = [0x20, 0x20, 0x20];
+const CAPTION: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+// A 3x5 pixel font, one row per byte with the leftmost pixel in bit 2. Lowercase
+// is drawn as uppercase and anything without a glyph as '?'.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ' ' => [0b000; GLYPH_HEIGHT as usize],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Tiles named thumbnails into a contact sheet `columns` cells wide, as
+/// R8G8B8A8. Each cell holds its thumbnail centred in a `size` pixel square
+/// with the name written underneath, cut short if it is wider than the cell.
+/// Thumbnails are expected to come from [`RawImage::thumbnail`] with the same
+/// `size`, anything larger is cropped.
+pub fn montage(
+    thumbnails: &[(String, RawImage)],
+    columns: u32,
+    size: u32,
+) -> TextureResult<RawImage> {
+    if columns == 0 || size == 0 || thumbnails.is_empty() {
+        return Err(Error::InvalidDimensions);
+    }
+
+    let count = u32::try_from(thumbnails.len()).map_err(|_| Error::InvalidDimensions)?;
+    let (columns, rows) = (columns.min(count), count.div_ceil(columns));
+    let cell_width = size + PADDING * 2;
+    let cell_height = size + GLYPH_HEIGHT + PADDING * 3;
+    let (width, height) = columns
+        .checked_mul(cell_width)
+        .zip(rows.checked_mul(cell_height))
+        .ok_or(Error::InvalidDimensions)?;
+
+    let pitch = width as usize * 4;
+    let mut pixels = [BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], 0xFF]
+        .repeat(width as usize * height as usize);
+    let mut put = |x: u32, y: u32, rgb: [u8; 3]| {
+        let i = y as usize * pitch + x as usize * 4;
+        pixels[i..i + 3].copy_from_slice(&rgb);
+    };
+
+    for (i, (name, thumbnail)) in (0..).zip(thumbnails) {
+        let (left, top) = ((i % columns) * cell_width, (i / columns) * cell_height);
+
+        let rgba = thumbnail.to_rgba8()?;
+        let (w, h) = (thumbnail.width.min(size), thumbnail.height.min(size));
+        let (x0, y0) = (
+            left + PADDING + (size - w) / 2,
+            top + PADDING + (size - h) / 2,
+        );
+        for y in 0..h {
+            for x in 0..w {
+                let j = (y * thumbnail.width + x) as usize * 4;
+                let alpha = rgba[j + 3] as u32;
+                let blend = |c: usize| {
+                    ((rgba[j + c] as u32 * alpha + BACKGROUND[c] as u32 * (0xFF - alpha)) / 0xFF)
+                        as u8
+                };
+                put(x0 + x, y0 + y, [blend(0), blend(1), blend(2)]);
+            }
+        }
+
+        let max_chars = ((size + 1) / (GLYPH_WIDTH + 1)) as usize;
+        let caption_top = top + size + PADDING * 2;
+        for (c, character) in (0..).zip(name.chars().take(max_chars)) {
+            let glyph_left = left + PADDING + c * (GLYPH_WIDTH + 1);
+            for (y, row) in (0..).zip(glyph(character)) {
+                for x in (0..GLYPH_WIDTH).filter(|x| row & (0b100 >> x) != 0) {
+                    put(glyph_left + x, caption_top + y, CAPTION);
+                }
+            }
+        }
+    }
+
+    Ok(RawImage {
+        width,
+        height,
+        pixels,
+        metadata: Metadata {
+            format: Format::R8G8B8A8,
+            ..Default::default()
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> RawImage {
+        RawImage {
+            width,
+            height,
+            pixels: rgba.repeat((width * height) as usize),
+            metadata: Metadata {
+                format: Format::R8G8B8A8,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn pixel(image: &RawImage, x: u32, y: u32) -> &[u8] {
+        let i = ((y * image.width + x) * 4) as usize;
+        &image.pixels[i..i + 4]
+    }
+
+    #[test]
+    fn tiles_thumbnails_with_captions() {
+        let thumbnails = [
+            ("A".to_string(), solid(8, 8, [0xFF, 0, 0, 0xFF])),
+            ("b".to_string(), solid(8, 4, [0, 0xFF, 0, 0xFF])),
+            ("c".to_string(), solid(2, 2, [0, 0, 0xFF, 0])),
+        ];
+        let sheet = montage(&thumbnails, 2, 8).unwrap();
+
+        // Two columns of 16x25 cells, the third thumbnail wrapping to a second row.
+        assert_eq!((sheet.width, sheet.height), (32, 50));
+        assert_eq!(sheet.metadata.format, Format::R8G8B8A8);
+        assert_eq!(pixel(&sheet, 0, 0), [0x20, 0x20, 0x20, 0xFF]);
+        assert_eq!(pixel(&sheet, 4, 4), [0xFF, 0, 0, 0xFF]);
+
+        // The shorter thumbnail is centred vertically.
+        assert_eq!(pixel(&sheet, 20, 5), [0x20, 0x20, 0x20, 0xFF]);
+        assert_eq!(pixel(&sheet, 20, 6), [0, 0xFF, 0, 0xFF]);
+
+        // A transparent thumbnail leaves the background showing.
+        assert_eq!(pixel(&sheet, 7, 32), [0x20, 0x20, 0x20, 0xFF]);
+
+        // The top row of 'A' is only its middle pixel, lowercase 'b' is drawn as 'B'.
+        assert_eq!(pixel(&sheet, 4, 16), [0x20, 0x20, 0x20, 0xFF]);
+        assert_eq!(pixel(&sheet, 5, 16), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(pixel(&sheet, 20, 16), [0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn long_names_are_cut_short() {
+        let short = montage(&[("MM".to_string(), solid(1, 1, [0; 4]))], 1, 8).unwrap();
+        let long = montage(&[("MMM".to_string(), solid(1, 1, [0; 4]))], 1, 8).unwrap();
+
+        assert_eq!(short.pixels, long.pixels);
+    }
+
+    #[test]
+    fn empty_montages_are_an_error() {
+        let thumbnails = [("A".to_string(), solid(1, 1, [0; 4]))];
+
+        assert!(matches!(montage(&[], 1, 8), Err(Error::InvalidDimensions)));
+        assert!(matches!(
+            montage(&thumbnails, 0, 8),
+            Err(Error::InvalidDimensions)
+        ));
+        assert!(matches!(
+            montage(&thumbnails, 1, 0),
+            Err(Error::InvalidDimensions)
+        ));
+    }
+}
@@ -1,9 +1,25 @@
 #![allow(dead_code)]
-use crate::Version;
-use bitchomp::{ByteWriter, Endianness};
-use texture2ddecoder::{decode_bc1, decode_bc3, decode_bc4, decode_bc5, decode_bc7};
+use crate::{
+    util::texture::{bits_per_pixel, pixel_block_size},
+    Version,
+};
+use bitchomp::{ByteReader, ByteWriter, ChompFlatten, Endianness};
+use half::f16;
+use intel_tex_2::{bc1, bc3, bc4, bc5, bc7, Surface};
+use texture2ddecoder::{
+    decode_bc1_block, decode_bc3_block, decode_bc4_block, decode_bc5_block, decode_bc7_block,
+};
 
-use super::{ColourType, Format, Type};
+use super::{ColourType, Error, Format, Type};
+
+// intel_tex has some C++ in it but doesn't link the C++ runtime itself. MSVC pulls
+// it in anyway, everywhere else needs it named.
+#[cfg_attr(
+    all(not(target_env = "msvc"), not(target_vendor = "apple")),
+    link(name = "stdc++")
+)]
+#[cfg_attr(target_vendor = "apple", link(name = "c++"))]
+extern "C" {}
 
 #[derive(Default, Debug, Clone)]
 pub struct Metadata {
@@ -13,6 +29,8 @@ pub struct Metadata {
     pub flags: u32,
     pub interpret_as: u8,
     pub interpol_mode: u16,
+    pub mips_count: u8,
+    pub default_mip: u8,
 }
 
 impl Metadata {
@@ -24,9 +42,37 @@ impl Metadata {
         buf.append(self.format as u16);
         buf.append(self.flags);
         buf.append(self.interpret_as);
+        // Added after the fields above, so older readers can still stop there.
+        buf.append(self.mips_count);
+        buf.append(self.default_mip);
 
         buf.buf()
     }
+
+    pub fn deserialize(buf: &mut ByteReader) -> Result<Self, Error> {
+        let mut metadata = Metadata {
+            version: match buf.read::<u8>()?.inner() {
+                0 => Version::H2016,
+                1 => Version::H2,
+                2 => Version::H3,
+                _ => Version::Unknown,
+            },
+            r#type: (buf.read::<u8>()?.inner() as u16).try_into()?,
+            // Left as Unknown like the loaders do, the pixels are still readable.
+            format: buf.read::<u16>()?.inner().try_into().unwrap_or_default(),
+            flags: buf.read()?.inner(),
+            interpret_as: buf.read()?.inner(),
+            ..Default::default()
+        };
+
+        // Older files end before the mips.
+        if buf.len() >= 2 {
+            metadata.mips_count = buf.read()?.inner();
+            metadata.default_mip = buf.read()?.inner();
+        }
+
+        Ok(metadata)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -84,107 +130,616 @@ impl Tony {
 
         buf.buf()
     }
-}
 
-fn get_image_pixels(img: RawImage) -> (ColourType, Vec<u8>) {
-    let mut pixels = vec![0_u32; (img.width * img.height) as usize];
-    let mut data: Vec<u8> = Vec::new();
-    let mut fix_channel = false;
+    /// Reads a TONY written by [`Tony::serialize`]. The pixels are decompressed
+    /// once to check them but kept compressed, so serializing again gives the
+    /// same bytes.
+    ///
+    /// ```
+    /// use tonytools::hmtextures::{structs::{Metadata, RawImage, Tony}, Format};
+    ///
+    /// let image = RawImage {
+    ///     width: 16,
+    ///     height: 8,
+    ///     pixels: (0..16 * 8 * 4).map(|i| i as u8).collect(),
+    ///     metadata: Metadata { format: Format::R8G8B8A8, mips_count: 5, ..Default::default() },
+    /// };
+    /// let tony = Tony::try_from(image.clone()).unwrap();
+    /// let bytes = tony.serialize();
+    ///
+    /// let read = Tony::deserialize(&bytes).unwrap();
+    /// assert_eq!((read.width, read.height), (16, 8));
+    /// assert_eq!(read.pixels().unwrap(), image.pixels);
+    /// assert_eq!(read.metadata.mips_count, 5);
+    /// assert_eq!(read.serialize(), bytes);
+    /// ```
+    pub fn deserialize(data: &[u8]) -> Result<Self, Error> {
+        let mut buf = ByteReader::new(data, Endianness::Little);
+
+        let magic: u32 = buf.read()?.inner();
+        if magic != 0x594E4F54 {
+            return Err(Error::InvalidMagic);
+        }
+
+        let colour_type: ColourType = buf.read::<u8>()?.inner().try_into()?;
+        let width: u32 = buf.read()?.inner();
+        let height: u32 = buf.read()?.inner();
+        let decompressed_size: u64 = buf.read()?.inner();
+        let compressed_size: u64 = buf.read()?.inner();
 
-    let mut colour = ColourType::Rgba8;
-    match img.metadata.format {
-        Format::R16G16B16A16 => {
-            colour = ColourType::Rgba16;
-            data = img.pixels.clone();
+        // The pixels are at most the image, and lz4 can't expand data more than
+        // 255 times. Checking both stops a bad size from allocating far more than
+        // the file could ever decompress to.
+        let size =
+            (width as u64 * height as u64).saturating_mul(colour_type.bytes_per_pixel() as u64);
+        if decompressed_size > size.min(compressed_size.saturating_mul(255))
+            || compressed_size > buf.len() as u64
+        {
+            return Err(Error::InvalidMipSizes);
         }
-        Format::R8G8B8A8 => {
-            data = img.pixels.clone();
+
+        let tony = Tony {
+            magic,
+            colour_type,
+            width,
+            height,
+            decompressed_size,
+            compressed_size,
+            data: buf.read_n::<u8>(compressed_size as usize)?.flatten(),
+            metadata: Metadata::deserialize(&mut buf)?,
+        };
+        tony.pixels()?;
+
+        Ok(tony)
+    }
+
+    // The decompressed pixels, laid out as colour_type says.
+    pub fn pixels(&self) -> Result<Vec<u8>, Error> {
+        let pixels = lz4_flex::block::decompress(&self.data, self.decompressed_size as usize)?;
+        if pixels.len() as u64 != self.decompressed_size {
+            return Err(Error::InvalidMipSizes);
         }
-        Format::R8G8 => {
-            colour = ColourType::Rgb8;
-            data = img
+
+        Ok(pixels)
+    }
+}
+
+// A rectangle of pixels as (x, y, width, height).
+type Region = (usize, usize, usize, usize);
+
+fn full_region(img: &RawImage) -> Region {
+    (0, 0, img.width as usize, img.height as usize)
+}
+
+// Block compressed formats are decoded one 4x4 block at a time straight into the
+// output, so we never hold a second full size buffer for large textures. Only
+// the blocks the region touches are decoded, then cropped to it.
+// L8 output keeps just the red channel. Data too short for the blocks is an error
+// rather than a partly blank image.
+fn decode_blocks(
+    img: &RawImage,
+    colour: ColourType,
+    block_size: usize,
+    decode_block: fn(&[u8], &mut [u32]),
+    fix_channel: bool,
+    (left, top, width, height): Region,
+) -> Result<Vec<u8>, Error> {
+    let blocks_x = (img.width as usize).div_ceil(4);
+    let bpp = colour.bytes_per_pixel();
+
+    let mut data = vec![0_u8; width * height * bpp];
+    let mut block = [0_u32; 16];
+
+    for by in top / 4..(top + height).div_ceil(4) {
+        for bx in left / 4..(left + width).div_ceil(4) {
+            let offset = (by * blocks_x + bx) * block_size;
+            let src = img
                 .pixels
-                .chunks_exact(2)
-                .flat_map(|e| [e[0], e[1], 0xFF])
-                .collect();
+                .get(offset..offset + block_size)
+                .ok_or(Error::DecodeError)?;
+
+            decode_block(src, &mut block);
+
+            for y in 0..4 {
+                let py = by * 4 + y;
+                if py < top {
+                    continue;
+                }
+                if py >= top + height {
+                    break;
+                }
+
+                for x in 0..4 {
+                    let px = bx * 4 + x;
+                    if px < left {
+                        continue;
+                    }
+                    if px >= left + width {
+                        break;
+                    }
+
+                    let v = block[y * 4 + x].to_le_bytes();
+                    let b = if fix_channel { 0xFF } else { v[0] };
+                    let i = ((py - top) * width + px - left) * bpp;
+                    data[i..i + bpp].copy_from_slice(&[v[2], v[1], b, v[3]][..bpp]);
+                }
+            }
         }
-        Format::A8 => {
-            colour = ColourType::L8;
-            data = img.pixels.clone();
+    }
+
+    Ok(data)
+}
+
+// R16G16B16A16 as half floats, for HDR textures like lightmaps.
+pub fn decode_half_float(pixels: &[u8]) -> Vec<f32> {
+    pixels
+        .chunks_exact(2)
+        .map(|e| f16::from_le_bytes([e[0], e[1]]).to_f32())
+        .collect()
+}
+
+// Reinhard tone mapping then gamma, so HDR data fits in 8 bits for viewing.
+// Alpha is just clamped.
+fn tone_map(pixels: &[u8]) -> Vec<u8> {
+    decode_half_float(pixels)
+        .chunks_exact(4)
+        .flat_map(|e| {
+            let map = |c: f32| {
+                let c = c.max(0.0);
+                ((c / (1.0 + c)).powf(1.0 / 2.2) * 255.0).round() as u8
+            };
+            [
+                map(e[0]),
+                map(e[1]),
+                map(e[2]),
+                (e[3].clamp(0.0, 1.0) * 255.0).round() as u8,
+            ]
+        })
+        .collect()
+}
+
+fn get_image_pixels(img: RawImage, half_float: bool) -> Result<(ColourType, Vec<u8>), Error> {
+    let colour = match img.metadata.format {
+        Format::R16G16B16A16 if half_float => ColourType::Rgba8,
+        Format::R16G16B16A16 => ColourType::Rgba16,
+        Format::R8G8 => ColourType::Rgb8,
+        Format::A8 | Format::BC4 => ColourType::L8,
+        _ => ColourType::Rgba8,
+    };
+
+    // Block formats check their own length as they go.
+    let size =
+        img.width as usize * img.height as usize * bits_per_pixel(img.metadata.format) as usize / 8;
+    if pixel_block_size(img.metadata.format) == 1 && img.pixels.len() < size {
+        return Err(Error::InvalidMipSizes);
+    }
+
+    let pixels = match img.metadata.format {
+        Format::R16G16B16A16 if half_float => tone_map(&img.pixels),
+        Format::R16G16B16A16 | Format::R8G8B8A8 | Format::A8 => img.pixels,
+        // Normals only store X and Y, Z is filled so the image isn't transparent.
+        Format::R8G8 => img
+            .pixels
+            .chunks_exact(2)
+            .flat_map(|e| [e[0], e[1], 0xFF])
+            .collect(),
+        Format::DXT1 => decode_blocks(&img, colour, 8, decode_bc1_block, false, full_region(&img))?,
+        Format::DXT5 => {
+            decode_blocks(&img, colour, 16, decode_bc3_block, false, full_region(&img))?
         }
-        Format::DXT1 => {
-            decode_bc1(
-                &img.pixels,
-                img.width as usize,
-                img.height as usize,
-                pixels.as_mut_slice(),
-            )
-            .unwrap();
+        Format::BC4 => decode_blocks(&img, colour, 8, decode_bc4_block, false, full_region(&img))?,
+        Format::BC5 => decode_blocks(&img, colour, 16, decode_bc5_block, true, full_region(&img))?,
+        Format::BC7 => decode_blocks(&img, colour, 16, decode_bc7_block, false, full_region(&img))?,
+        Format::Unknown => return Err(Error::UnknownFormat),
+    };
+
+    Ok((colour, pixels))
+}
+
+impl RawImage {
+    // Re-encodes the top mip in another format, e.g. an imported RGBA8 image as
+    // BC7. The pixels are decoded to RGBA8 first, so going from a 16 bit format
+    // loses precision. Block compressed targets are encoded with intel_tex.
+    pub fn rebuild_as(&self, target: Format) -> Result<RawImage, Error> {
+        let mut metadata = self.metadata.clone();
+        metadata.format = target;
+
+        if target == self.metadata.format {
+            return Ok(RawImage {
+                metadata,
+                ..self.clone()
+            });
         }
-        Format::DXT5 => {
-            decode_bc3(
-                &img.pixels,
-                img.width as usize,
-                img.height as usize,
-                pixels.as_mut_slice(),
-            )
-            .unwrap();
+
+        let rgba = self.to_rgba8()?;
+        let (width, height) = (self.width, self.height);
+        let channel = |i: usize| rgba.iter().skip(i).step_by(4).copied();
+
+        let pixels = match target {
+            Format::R8G8B8A8 => rgba,
+            Format::R16G16B16A16 => rgba
+                .iter()
+                .flat_map(|v| (*v as u16 * 257).to_le_bytes())
+                .collect(),
+            Format::R8G8 => rgba.chunks_exact(4).flat_map(|e| [e[0], e[1]]).collect(),
+            Format::A8 => channel(0).collect(),
+            Format::DXT1 => encode_blocks(&rgba, width, height, 4, bc1::compress_blocks),
+            Format::DXT5 => encode_blocks(&rgba, width, height, 4, bc3::compress_blocks),
+            Format::BC7 => encode_blocks(&rgba, width, height, 4, |s| {
+                bc7::compress_blocks(&bc7::alpha_basic_settings(), s)
+            }),
+            Format::BC4 => {
+                let r = channel(0).collect::<Vec<_>>();
+                encode_blocks(&r, width, height, 1, bc4::compress_blocks)
+            }
+            Format::BC5 => {
+                let rg = rgba
+                    .chunks_exact(4)
+                    .flat_map(|e| [e[0], e[1]])
+                    .collect::<Vec<_>>();
+                encode_blocks(&rg, width, height, 2, bc5::compress_blocks)
+            }
+            Format::Unknown => return Err(Error::UnknownFormat),
+        };
+
+        Ok(RawImage {
+            width,
+            height,
+            pixels,
+            metadata,
+        })
+    }
+
+    // Decodes just the part of the top mip covering the rectangle, e.g. one icon
+    // from a large UI atlas. Block compressed formats only decode the 4x4 blocks
+    // the rectangle touches and come out as R8G8B8A8, or A8 for BC4. Uncompressed
+    // formats are sliced out by row and keep their format.
+    pub fn decode_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<RawImage, Error> {
+        let fits = |start: u32, len: u32, max: u32| {
+            len != 0 && start.checked_add(len).is_some_and(|end| end <= max)
+        };
+        if !fits(x, width, self.width) || !fits(y, height, self.height) {
+            return Err(Error::InvalidDimensions);
         }
-        Format::BC4 => {
-            colour = ColourType::L8;
-
-            decode_bc4(
-                &img.pixels,
-                img.width as usize,
-                img.height as usize,
-                pixels.as_mut_slice(),
-            )
-            .unwrap();
+
+        let region = (x as usize, y as usize, width as usize, height as usize);
+        let (format, pixels) = match self.metadata.format {
+            format @ (Format::R16G16B16A16 | Format::R8G8B8A8 | Format::R8G8 | Format::A8) => {
+                let bpp = bits_per_pixel(format) as usize / 8;
+                let pitch = self.width as usize * bpp;
+                let (left, right) = (region.0 * bpp, (region.0 + region.2) * bpp);
+
+                if self.pixels.len() < (region.1 + region.3 - 1) * pitch + right {
+                    return Err(Error::InvalidMipSizes);
+                }
+
+                let rows = self.pixels.chunks(pitch).skip(region.1).take(region.3);
+                (
+                    format,
+                    rows.flat_map(|row| &row[left..right]).copied().collect(),
+                )
+            }
+            Format::DXT1 => (
+                Format::R8G8B8A8,
+                decode_blocks(self, ColourType::Rgba8, 8, decode_bc1_block, false, region)?,
+            ),
+            Format::DXT5 => (
+                Format::R8G8B8A8,
+                decode_blocks(self, ColourType::Rgba8, 16, decode_bc3_block, false, region)?,
+            ),
+            Format::BC4 => (
+                Format::A8,
+                decode_blocks(self, ColourType::L8, 8, decode_bc4_block, false, region)?,
+            ),
+            Format::BC5 => (
+                Format::R8G8B8A8,
+                decode_blocks(self, ColourType::Rgba8, 16, decode_bc5_block, true, region)?,
+            ),
+            Format::BC7 => (
+                Format::R8G8B8A8,
+                decode_blocks(self, ColourType::Rgba8, 16, decode_bc7_block, false, region)?,
+            ),
+            Format::Unknown => return Err(Error::UnknownFormat),
+        };
+
+        let mut metadata = self.metadata.clone();
+        metadata.format = format;
+
+        Ok(RawImage {
+            width,
+            height,
+            pixels,
+            metadata,
+        })
+    }
+
+    /// Scales the top mip down to fit within `size` pixels square, keeping its
+    /// aspect ratio, as R8G8B8A8. Each pixel is the average of the pixels it
+    /// covers. Images that already fit keep their size.
+    ///
+    /// ```
+    /// use tonytools::hmtextures::{structs::{Metadata, RawImage}, Format};
+    ///
+    /// let image = RawImage {
+    ///     width: 512,
+    ///     height: 256,
+    ///     pixels: vec![0x80; 512 * 256 * 4],
+    ///     metadata: Metadata { format: Format::R8G8B8A8, ..Default::default() },
+    /// };
+    /// let thumbnail = image.rebuild_as(Format::DXT1).unwrap().thumbnail(64).unwrap();
+    ///
+    /// assert_eq!((thumbnail.width, thumbnail.height), (64, 32));
+    /// assert_eq!(thumbnail.metadata.format, Format::R8G8B8A8);
+    /// ```
+    pub fn thumbnail(&self, size: u32) -> Result<RawImage, Error> {
+        if size == 0 || self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidDimensions);
         }
-        Format::BC5 => {
-            fix_channel = true;
-
-            decode_bc5(
-                &img.pixels,
-                img.width as usize,
-                img.height as usize,
-                pixels.as_mut_slice(),
-            )
-            .unwrap();
+
+        let rgba = self.to_rgba8()?;
+        let (w, h, size) = (self.width as u64, self.height as u64, size as u64);
+        let (tw, th) = match (w, h) {
+            (w, h) if w <= size && h <= size => (w, h),
+            (w, h) if w >= h => (size, (h * size / w).max(1)),
+            (w, h) => ((w * size / h).max(1), size),
+        };
+
+        let mut pixels = Vec::with_capacity((tw * th * 4) as usize);
+        for ty in 0..th {
+            let (y0, y1) = ((ty * h / th) as usize, ((ty + 1) * h / th) as usize);
+            for tx in 0..tw {
+                let (x0, x1) = ((tx * w / tw) as usize, ((tx + 1) * w / tw) as usize);
+
+                let mut sum = [0_u64; 4];
+                for y in y0..y1 {
+                    let row = y * w as usize;
+                    for pixel in rgba[(row + x0) * 4..(row + x1) * 4].chunks_exact(4) {
+                        for (sum, channel) in sum.iter_mut().zip(pixel) {
+                            *sum += *channel as u64;
+                        }
+                    }
+                }
+
+                let count = ((y1 - y0) * (x1 - x0)) as u64;
+                pixels.extend(sum.map(|sum| (sum / count) as u8));
+            }
         }
-        Format::BC7 => {
-            decode_bc7(
-                &img.pixels,
-                img.width as usize,
-                img.height as usize,
-                pixels.as_mut_slice(),
-            )
-            .unwrap();
+
+        let mut metadata = self.metadata.clone();
+        metadata.format = Format::R8G8B8A8;
+
+        Ok(RawImage {
+            width: tw as u32,
+            height: th as u32,
+            pixels,
+            metadata,
+        })
+    }
+
+    /// Decodes the top mip and encodes it as a PNG. R16G16B16A16 is written as a
+    /// 16 bit PNG, A8 and BC4 as greyscale and R8G8 as RGB.
+    ///
+    /// ```
+    /// use tonytools::hmtextures::{structs::{Metadata, RawImage}, Format};
+    ///
+    /// let rgba = RawImage {
+    ///     width: 64,
+    ///     height: 32,
+    ///     pixels: vec![0x80; 64 * 32 * 4],
+    ///     metadata: Metadata { format: Format::R8G8B8A8, ..Default::default() },
+    /// };
+    /// let png = rgba.rebuild_as(Format::BC7).unwrap().to_png().unwrap();
+    ///
+    /// assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+    /// // The IHDR chunk's width and height.
+    /// assert_eq!(png[16..24], [0, 0, 0, 64, 0, 0, 0, 32]);
+    /// ```
+    #[cfg(feature = "png")]
+    pub fn to_png(&self) -> super::TextureResult<Vec<u8>> {
+        use image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder};
+
+        let (colour, pixels) = get_image_pixels(self.clone(), false)?;
+        let (colour_type, pixels) = match colour {
+            ColourType::L8 => (ExtendedColorType::L8, pixels),
+            ColourType::Rgb8 => (ExtendedColorType::Rgb8, pixels),
+            ColourType::Rgba8 => (ExtendedColorType::Rgba8, pixels),
+            // The encoder takes 16 bit channels in native endian.
+            ColourType::Rgba16 => (
+                ExtendedColorType::Rgba16,
+                pixels
+                    .chunks_exact(2)
+                    .flat_map(|e| u16::from_le_bytes([e[0], e[1]]).to_ne_bytes())
+                    .collect(),
+            ),
+        };
+
+        let size = self.width as usize * self.height as usize * colour.bytes_per_pixel();
+        let pixels = pixels.get(..size).ok_or(Error::InvalidMipSizes)?;
+
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).write_image(pixels, self.width, self.height, colour_type)?;
+
+        Ok(png)
+    }
+
+    // Every format as RGBA8. Greyscale is spread over RGB and 16 bit channels
+    // keep their high byte.
+    pub(crate) fn to_rgba8(&self) -> Result<Vec<u8>, Error> {
+        let (colour, pixels) = get_image_pixels(self.clone(), false)?;
+        let rgba: Vec<u8> = match colour {
+            ColourType::L8 => pixels.iter().flat_map(|l| [*l, *l, *l, 0xFF]).collect(),
+            ColourType::Rgb8 => pixels
+                .chunks_exact(3)
+                .flat_map(|e| [e[0], e[1], e[2], 0xFF])
+                .collect(),
+            ColourType::Rgba8 => pixels,
+            ColourType::Rgba16 => pixels.chunks_exact(2).map(|e| e[1]).collect(),
+        };
+
+        if rgba.len() < (self.width * self.height * 4) as usize {
+            return Err(Error::InvalidMipSizes);
         }
-        _ => {}
+
+        Ok(rgba)
     }
+}
 
-    match img.metadata.format {
-        Format::R16G16B16A16 | Format::R8G8B8A8 | Format::R8G8 | Format::A8 => {}
-        _ => {
-            data = pixels
-                .iter()
-                .flat_map(|x| {
-                    let v = x.to_le_bytes();
-                    let b = if fix_channel { 0xFF } else { v[0] };
-                    [v[2], v[1], b, v[3]]
-                })
-                .collect();
+// The encoders only take whole 4x4 blocks, so the edge pixels are repeated out to
+// a multiple of 4 first.
+fn encode_blocks<const N: usize>(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    channels: usize,
+    encode: impl Fn(&Surface<N>) -> Vec<u8>,
+) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let (padded_w, padded_h) = (w.next_multiple_of(4), h.next_multiple_of(4));
+
+    let mut padded = Vec::with_capacity(padded_w * padded_h * channels);
+    for y in 0..padded_h {
+        let row = y.min(h - 1) * w;
+        for x in 0..padded_w {
+            let i = (row + x.min(w - 1)) * channels;
+            padded.extend_from_slice(&pixels[i..i + channels]);
         }
     }
 
-    (colour, data)
+    encode(&Surface {
+        data: &padded,
+        width: padded_w as u32,
+        height: padded_h as u32,
+        stride: (padded_w * channels) as u32,
+    })
 }
 
-impl From<RawImage> for Tony {
-    fn from(img: RawImage) -> Self {
-        let (colour, data) = get_image_pixels(img.clone());
+impl Tony {
+    // R16G16B16A16 is normally kept as 16 bit integers. HDR textures store half
+    // floats there instead, which nothing in the TEXT marks, so with half_float
+    // they're tone mapped down to Rgba8.
+    pub fn from_image(img: RawImage, half_float: bool) -> Result<Self, Error> {
+        let (width, height, metadata) = (img.width, img.height, img.metadata.clone());
+        let (colour, data) = get_image_pixels(img, half_float)?;
+
+        Ok(Tony::new(colour, width, height, data, metadata))
+    }
+}
+
+impl TryFrom<RawImage> for Tony {
+    type Error = self::Error;
+    fn try_from(img: RawImage) -> Result<Self, Self::Error> {
+        Tony::from_image(img, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(format: Format, width: u32, height: u32, pixels: Vec<u8>) -> RawImage {
+        RawImage {
+            width,
+            height,
+            pixels,
+            metadata: Metadata {
+                format,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn corrupt_bc7_blocks_decode() {
+        // Any 16 bytes are a valid BC7 block, garbage just gives garbage pixels.
+        let pixels = (0..4 * 16).map(|i| (i * 37 + 11) as u8).collect();
+        let tony = Tony::try_from(image(Format::BC7, 8, 8, pixels)).unwrap();
+
+        assert_eq!(tony.decompressed_size, 8 * 8 * 4);
+    }
+
+    #[test]
+    fn truncated_bc7_is_an_error() {
+        // Three of the four blocks an 8x8 image needs.
+        let img = image(Format::BC7, 8, 8, vec![0x20; 3 * 16]);
+
+        assert!(matches!(
+            Tony::try_from(img.clone()),
+            Err(Error::DecodeError)
+        ));
+        assert!(matches!(
+            img.decode_region(4, 4, 4, 4),
+            Err(Error::DecodeError)
+        ));
+        // The blocks that are there can still be read.
+        assert!(img.decode_region(0, 0, 8, 4).is_ok());
+    }
+
+    #[test]
+    fn truncated_uncompressed_is_an_error() {
+        let img = image(Format::R8G8B8A8, 8, 8, vec![0; 8 * 8 * 4 - 1]);
+
+        assert!(matches!(Tony::try_from(img), Err(Error::InvalidMipSizes)));
+    }
+
+    #[test]
+    fn hdr_lightmap_tone_maps_to_rgba8() {
+        // Two half float texels, one in range and one far brighter with negative red.
+        let texels = [[0.0, 1.0, 3.0, 1.0], [-1.0, 1000.0, 1000.0, 2.0]];
+        let pixels: Vec<u8> = texels
+            .iter()
+            .flatten()
+            .flat_map(|&c: &f32| f16::from_f32(c).to_le_bytes())
+            .collect();
+
+        assert_eq!(decode_half_float(&pixels), texels.concat());
+
+        let hdr = Tony::from_image(image(Format::R16G16B16A16, 2, 1, pixels.clone()), true);
+        let hdr = hdr.unwrap();
+        assert!(matches!(hdr.colour_type, ColourType::Rgba8));
+        assert_eq!(hdr.pixels().unwrap(), [0, 186, 224, 255, 0, 255, 255, 255]);
+
+        // Integer Rgba16 stays the default, with the bytes as they are.
+        let raw = Tony::try_from(image(Format::R16G16B16A16, 2, 1, pixels.clone())).unwrap();
+        assert!(matches!(raw.colour_type, ColourType::Rgba16));
+        assert_eq!(raw.pixels().unwrap(), pixels);
+    }
+
+    #[test]
+    fn thumbnails_average_the_pixels_they_cover() {
+        // Left half black, right half white, with a grey alpha.
+        let pixels = (0..4 * 2)
+            .flat_map(|i| {
+                if i % 4 < 2 {
+                    [0, 0, 0, 0x40]
+                } else {
+                    [0xFF, 0xFF, 0xFF, 0x80]
+                }
+            })
+            .collect();
+        let thumbnail = image(Format::R8G8B8A8, 4, 2, pixels).thumbnail(2).unwrap();
+
+        assert_eq!((thumbnail.width, thumbnail.height), (2, 1));
+        assert_eq!(thumbnail.pixels, [0, 0, 0, 0x40, 0xFF, 0xFF, 0xFF, 0x80]);
+    }
+
+    #[test]
+    fn thumbnails_are_not_upscaled() {
+        let thumbnail = image(Format::A8, 3, 2, vec![0x10; 6])
+            .thumbnail(64)
+            .unwrap();
 
-        Tony::new(colour, img.width, img.height, data, img.metadata)
+        assert_eq!((thumbnail.width, thumbnail.height), (3, 2));
+        assert_eq!(thumbnail.metadata.format, Format::R8G8B8A8);
+        assert_eq!(thumbnail.pixels, [0x10, 0x10, 0x10, 0xFF].repeat(6));
+        assert!(matches!(
+            image(Format::A8, 3, 2, vec![0x10; 6]).thumbnail(0),
+            Err(Error::InvalidDimensions)
+        ));
     }
 }
@@ -0,0 +1,179 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use log::{error, info, warn, LevelFilter};
+use tonytools::{
+    hmtextures::{montage::montage, Texture},
+    Version,
+};
+
+#[derive(ValueEnum, Clone, Debug)]
+enum GameVersion {
+    H3,
+    H2,
+    H2016,
+}
+
+impl From<GameVersion> for Version {
+    fn from(version: GameVersion) -> Self {
+        match version {
+            GameVersion::H3 => Version::H3,
+            GameVersion::H2 => Version::H2,
+            GameVersion::H2016 => Version::H2016,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(value_enum)]
+    version: GameVersion,
+
+    /// More output per use, -v for debug and -vv for trace. RUST_LOG overrides both.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only errors.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Prints the format, type, dimensions and mip count from a TEXT's header.
+    Info { input: PathBuf },
+    /// Tiles a thumbnail of every TEXT in a folder into one captioned PNG. A TEXD
+    /// next to a TEXT with the same name is used for its pixels.
+    Montage {
+        input: PathBuf,
+
+        output: PathBuf,
+
+        /// Thumbnails per row.
+        #[clap(long)]
+        cols: u32,
+
+        /// Largest thumbnail side in pixels. Thumbnails are scaled from the top mip.
+        #[clap(long)]
+        #[clap(default_value_t = 128)]
+        size: u32,
+    },
+}
+
+fn main() {
+    let exit_code = real_main();
+    std::process::exit(exit_code);
+}
+
+fn real_main() -> i32 {
+    let args = Args::parse();
+
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let version: Version = args.version.into();
+
+    match args.cmd {
+        Commands::Info { input } => {
+            let Ok(data) = fs::read(&input) else {
+                error!("Input path is invalid.");
+                return 1;
+            };
+
+            let texture = match Texture::read(version, &data, None) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    error!("Failed to read texture {:?}.", e);
+                    return 1;
+                }
+            };
+
+            let metadata = texture.metadata();
+            let (width, height) = texture.dimensions();
+            println!("Format: {}", metadata.format);
+            println!("Type: {}", metadata.r#type);
+            println!("Dimensions: {}x{}", width, height);
+            println!("Mips: {}", metadata.mips_count);
+        }
+        Commands::Montage {
+            input,
+            output,
+            cols,
+            size,
+        } => {
+            let Ok(entries) = fs::read_dir(&input) else {
+                error!("Input path is invalid.");
+                return 1;
+            };
+
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("TEXT"))
+                })
+                .collect();
+            paths.sort();
+
+            let mut thumbnails = Vec::with_capacity(paths.len());
+            for path in paths {
+                let Ok(data) = fs::read(&path) else {
+                    warn!("Failed to read {}, skipping.", path.display());
+                    continue;
+                };
+                let texd = fs::read(path.with_extension("TEXD"))
+                    .or_else(|_| fs::read(path.with_extension("texd")))
+                    .ok();
+
+                let thumbnail = Texture::load(version, &data, texd.as_deref())
+                    .and_then(|image| image.thumbnail(size));
+                match thumbnail {
+                    Ok(thumbnail) => {
+                        let name = path.file_stem().unwrap_or_default();
+                        thumbnails.push((name.to_string_lossy().into_owned(), thumbnail));
+                    }
+                    Err(e) => warn!("Failed to load {}, skipping: {:?}.", path.display(), e),
+                }
+            }
+
+            if thumbnails.is_empty() {
+                error!("No textures found in {}.", input.display());
+                return 1;
+            }
+
+            let png = montage(&thumbnails, cols, size).and_then(|sheet| sheet.to_png());
+            match png {
+                Ok(png) => {
+                    if fs::write(&output, png).is_err() {
+                        error!("Failed to write montage.");
+                        return 1;
+                    }
+                    info!(
+                        "Wrote {} thumbnails to {}.",
+                        thumbnails.len(),
+                        output.display()
+                    );
+                }
+                Err(e) => {
+                    error!("Failed to build montage {:?}.", e);
+                    return 1;
+                }
+            }
+        }
+    }
+
+    0
+}
@@ -2,7 +2,7 @@
 #![feature(generic_const_exprs)]
 
 pub mod hmlanguages;
-pub(crate) mod hmtextures;
+pub mod hmtextures;
 pub(crate) mod util;
 
 #[derive(Default, Debug, PartialEq, Copy, Clone)]
@@ -15,3 +15,6 @@ pub enum Version {
 }
 
 pub use hmlanguages::*;
+pub use util::cipher;
+pub use util::rpkg;
+pub use util::texture;
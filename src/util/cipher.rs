@@ -2,25 +2,57 @@ use byteorder::LE;
 use extended_tea::XTEA;
 use once_cell::sync::Lazy;
 
-use crate::hmlanguages::LangResult;
+use crate::hmlanguages::{LangError, LangResult};
 
 static XTEA: Lazy<XTEA> =
     Lazy::new(|| XTEA::new(&[0x53527737u32, 0x7506499Eu32, 0xBD39AEE3u32, 0xA59E7268u32]));
 
+/// XTEA works on 8 byte blocks, so encrypted strings are padded to a multiple of this.
+pub const XTEA_BLOCK_SIZE: usize = 8;
+
+/// The length of `len` bytes once encrypted with `xtea_encrypt`.
+///
+/// ```
+/// use tonytools::cipher::{padded_len, xtea_encrypt};
+///
+/// assert_eq!(padded_len(0), 0);
+/// assert_eq!(padded_len(5), 8);
+/// assert_eq!(padded_len(16), 16);
+/// assert_eq!(xtea_encrypt("Hello there").len(), padded_len("Hello there".len()));
+/// ```
+pub fn padded_len(len: usize) -> usize {
+    len.next_multiple_of(XTEA_BLOCK_SIZE)
+}
+
+/// Decrypts XTEA data, trimming the NUL padding added by `xtea_encrypt`.
 pub fn xtea_decrypt(data: Vec<u8>) -> LangResult<String> {
+    Ok(xtea_decrypt_raw(data)?
+        .trim_matches(char::from(0))
+        .to_string())
+}
+
+/// Decrypts XTEA data as-is, keeping any NUL padding. Data that isn't a whole
+/// number of blocks is `InvalidInput`.
+pub fn xtea_decrypt_raw(data: Vec<u8>) -> LangResult<String> {
+    if !data.len().is_multiple_of(XTEA_BLOCK_SIZE) {
+        return Err(LangError::InvalidInput);
+    }
+
     let mut out_data = data.clone();
 
     XTEA.decipher_u8slice::<LE>(&data, &mut out_data);
-    Ok(String::from_utf8(out_data)?
-        .trim_matches(char::from(0))
-        .to_string())
+    Ok(String::from_utf8(out_data)?)
 }
 
+/// Encrypts a string with XTEA. The input is NUL padded so the output is
+/// always a multiple of 8 bytes long.
+///
+/// Input that is already a multiple of 8 isn't padded further. Text decrypted
+/// with `xtea_decrypt_raw` keeps whatever padding the file had, so encrypting
+/// it again gives back the original length.
 pub fn xtea_encrypt(str: &str) -> Vec<u8> {
     let mut str = str.as_bytes().to_vec();
-    if str.len() % 8 != 0 {
-        str.extend(vec![0; 8 - (str.len() % 8)]);
-    }
+    str.resize(padded_len(str.len()), 0);
 
     let mut out_data = vec![0; str.len()];
 
@@ -29,11 +61,12 @@ pub fn xtea_encrypt(str: &str) -> Vec<u8> {
     out_data
 }
 
+/// Encrypts data with the bit-shuffling cipher used by H2016 LOCR files.
+/// The output is the same length as the input, no padding is added.
 pub fn symmetric_encrypt(data: Vec<u8>) -> Vec<u8> {
     let mut data = data.clone();
     for char in data.as_mut_slice() {
-        let value = *char;
-        *char ^= 226;
+        let value = *char ^ 226;
         *char = (value & 0x81)
             | (value & 2) << 1
             | (value & 4) << 2
@@ -46,6 +79,7 @@ pub fn symmetric_encrypt(data: Vec<u8>) -> Vec<u8> {
     data
 }
 
+/// Decrypts data encrypted with `symmetric_encrypt`.
 pub fn symmetric_decrypt(mut data: Vec<u8>) -> LangResult<String> {
     for char in data.as_mut_slice() {
         let value = *char;
@@ -62,3 +96,36 @@ pub fn symmetric_decrypt(mut data: Vec<u8>) -> LangResult<String> {
 
     Ok(String::from_utf8(data)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_round_trip_keeps_the_padding() {
+        // Aligned text, padding inside the last block and an extra full block.
+        let blocks = [
+            xtea_encrypt("Subtitle"),
+            xtea_encrypt("Hi"),
+            xtea_encrypt("Subtitle\0\0\0\0\0\0\0\0"),
+        ];
+        assert_eq!(blocks.each_ref().map(|b| b.len()), [8, 8, 16]);
+
+        for block in blocks {
+            let text = xtea_decrypt_raw(block.clone()).unwrap();
+            assert_eq!(xtea_encrypt(&text), block);
+        }
+    }
+
+    #[test]
+    fn partial_blocks_are_an_error() {
+        assert!(matches!(
+            xtea_decrypt_raw(vec![0; 5]),
+            Err(LangError::InvalidInput)
+        ));
+        assert!(matches!(
+            xtea_decrypt(vec![0; 9]),
+            Err(LangError::InvalidInput)
+        ));
+    }
+}
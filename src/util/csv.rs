@@ -0,0 +1,134 @@
+use crate::hmlanguages::{LangError, LangResult};
+
+// Minimal RFC 4180 reading and writing for the spreadsheet exports, fields are
+// quoted when they contain a comma, quote or line break.
+pub(crate) fn write_row(out: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+
+        if field.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+
+    out.push_str("\r\n");
+}
+
+// Returns each record with the line it starts on. Excel saves UTF-8 CSVs with a
+// BOM so that's skipped, and both CRLF and LF line endings are accepted.
+pub(crate) fn parse(input: &str) -> LangResult<Vec<(usize, Vec<String>)>> {
+    let input = input.strip_prefix('\u{FEFF}').unwrap_or(input);
+
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => {
+                quoted = false;
+                if !matches!(chars.peek(), None | Some(',' | '\r' | '\n')) {
+                    return Err(LangError::InvalidCsv { line });
+                }
+            }
+            (true, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, '"') => return Err(LangError::InvalidCsv { line }),
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            (false, c) => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(LangError::InvalidCsv { line: start });
+    }
+
+    // The last record may not end with a line break.
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRICKY: [&str; 6] = [
+        "plain",
+        "a, b",
+        "say \"hi\"",
+        "first\nsecond",
+        "first\r\nsecond",
+        "",
+    ];
+
+    #[test]
+    fn tricky_fields_round_trip() {
+        let mut csv = String::new();
+        write_row(&mut csv, &TRICKY);
+        write_row(&mut csv, &["last", "row"]);
+
+        let records = parse(&csv).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], (1, TRICKY.map(String::from).to_vec()));
+        // The quoted line breaks in the first record push the second down.
+        assert_eq!(records[1], (4, vec!["last".to_string(), "row".to_string()]));
+    }
+
+    #[test]
+    fn excel_exports_parse() {
+        let records = parse("\u{FEFF}a,\"b\"\"c\"\nd,e").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                (1, vec!["a".to_string(), "b\"c".to_string()]),
+                (2, vec!["d".to_string(), "e".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_quotes_are_an_error() {
+        assert!(matches!(
+            parse("a,b\"c\n"),
+            Err(LangError::InvalidCsv { line: 1 })
+        ));
+        assert!(matches!(
+            parse("a\n\"b\"c\n"),
+            Err(LangError::InvalidCsv { line: 2 })
+        ));
+        assert!(matches!(
+            parse("a\n\"b,\nc"),
+            Err(LangError::InvalidCsv { line: 2 })
+        ));
+    }
+}
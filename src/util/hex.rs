@@ -0,0 +1,23 @@
+// Hashes are written as zero padded uppercase hex, 8 digits for the 32-bit line,
+// tag and wav hashes and 16 for 64-bit resource hashes and RIDs. Parsing takes
+// 1 up to that many hex digits and nothing else, so a 64-bit hash isn't quietly
+// read as a 32-bit one.
+pub(crate) fn u32_to_hex(value: u32) -> String {
+    format!("{:08X}", value)
+}
+
+pub(crate) fn u32_from_hex(hex: &str) -> Option<u32> {
+    is_hex(hex, 8).then(|| u32::from_str_radix(hex, 16).ok()).flatten()
+}
+
+pub(crate) fn u64_to_hex(value: u64) -> String {
+    format!("{:016X}", value)
+}
+
+pub(crate) fn u64_from_hex(hex: &str) -> Option<u64> {
+    is_hex(hex, 16).then(|| u64::from_str_radix(hex, 16).ok()).flatten()
+}
+
+fn is_hex(hex: &str, max_digits: usize) -> bool {
+    (1..=max_digits).contains(&hex.len()) && hex.bytes().all(|b| b.is_ascii_hexdigit())
+}
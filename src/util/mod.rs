@@ -1,4 +1,7 @@
 pub mod cipher;
+pub(crate) mod csv;
+pub(crate) mod hex;
+pub(crate) mod reader;
 pub mod rpkg;
 pub mod texture;
 
@@ -0,0 +1,32 @@
+use std::io;
+
+use bitchomp::{
+    ByteReader, ByteReaderError, ByteReaderErrorKind, ByteReaderResource, ChompFlatten,
+};
+
+pub(crate) trait ByteReaderExt<'a> {
+    // Reads a u32 element count followed by that many T. read_n already fails
+    // if the buffer is too short, max_elems additionally caps the count.
+    fn read_sized_vec<T: ByteReaderResource<'a> + Copy>(
+        &mut self,
+        max_elems: Option<usize>,
+    ) -> Result<Vec<T>, ByteReaderError>;
+}
+
+impl<'a> ByteReaderExt<'a> for ByteReader<'a> {
+    fn read_sized_vec<T: ByteReaderResource<'a> + Copy>(
+        &mut self,
+        max_elems: Option<usize>,
+    ) -> Result<Vec<T>, ByteReaderError> {
+        let size = self.read::<u32>()?.inner() as usize;
+
+        if max_elems.is_some_and(|max| size > max) {
+            return Err(self.err(ByteReaderErrorKind::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("sized vector of {} elements exceeds the limit", size),
+            ))));
+        }
+
+        Ok(self.read_n::<T>(size)?.flatten())
+    }
+}
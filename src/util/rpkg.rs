@@ -2,6 +2,9 @@ use fancy_regex::Regex;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "rpkg")]
+use super::hex::u64_from_hex;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResourceMeta {
     pub hash_offset: u64,
@@ -49,6 +52,14 @@ impl ResourceMeta {
                 .collect(),
         }
     }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,12 +68,91 @@ pub struct ResourceDependency {
     pub flag: String,
 }
 
+// A resource in the numeric form RPKG writers take, rather than the strings of
+// the meta JSON, so it can be handed to an RPKG crate without going via disk.
+#[cfg(feature = "rpkg")]
+#[derive(Debug, Clone)]
+pub struct PackageResource {
+    pub rid: u64,
+    pub four_cc: [u8; 4],
+    pub data: Vec<u8>,
+    pub references: Vec<PackageReference>,
+}
+
+#[cfg(feature = "rpkg")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackageReference {
+    pub rid: u64,
+    pub flag: u8,
+}
+
+#[cfg(feature = "rpkg")]
+impl ResourceMeta {
+    // Depends given as paths are hashed, since packages only store the IDs.
+    // Returns None if the hash, type or a flag can't be represented.
+    pub fn to_package(&self, data: Vec<u8>) -> Option<PackageResource> {
+        let rid = |hash: &str| match is_valid_hash(hash) {
+            true => u64_from_hex(hash),
+            false => u64_from_hex(&compute_hash(hash)),
+        };
+
+        Some(PackageResource {
+            rid: rid(&self.hash_value)?,
+            four_cc: self.hash_resource_type.as_bytes().try_into().ok()?,
+            data,
+            references: self
+                .hash_reference_data
+                .iter()
+                .map(|depend| {
+                    Some(PackageReference {
+                        rid: rid(&depend.hash)?,
+                        flag: u8::from_str_radix(&depend.flag, 16).ok()?,
+                    })
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
 pub fn is_valid_hash(hash: &str) -> bool {
     let re = Regex::new(r"^[0-9A-F]{16}$").unwrap();
     re.is_match(hash).unwrap()
 }
 
+// Depends are accepted as a 16 digit hash or an IOI path like
+// [assembly:/sound/wwise/exported/dialog.wav].pc_wes, both of which RPKG tools
+// understand. Paths are kept so the names survive a convert. Anything else, like a
+// bare wav name, is hashed, the same as RTLV video names. Hashes can be typed in
+// either case and come out uppercase.
+pub fn normalize_depend(depend: &str) -> String {
+    let upper = depend.to_uppercase();
+    if is_valid_hash(&upper) {
+        upper
+    } else if depend.starts_with('[') {
+        depend.to_string()
+    } else {
+        compute_hash(depend)
+    }
+}
+
 pub fn compute_hash(hash: &str) -> String {
     let hash = format!("{:X}", md5::compute(hash));
     format!("00{}", &hash[2..16])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_depend_forms() {
+        assert_eq!(normalize_depend("00ABCDEF01234567"), "00ABCDEF01234567");
+        assert_eq!(normalize_depend("00abcdef01234567"), "00ABCDEF01234567");
+
+        let path = "[assembly:/sound/wwise/exported/dialog.wav].pc_wes";
+        assert_eq!(normalize_depend(path), path);
+
+        assert_eq!(normalize_depend("dialog_line"), compute_hash("dialog_line"));
+        assert!(is_valid_hash(&normalize_depend("dialog_line")));
+    }
+}
@@ -1,10 +1,23 @@
-#![allow(dead_code)]
+//! Size and layout helpers for Glacier 2 textures.
+//!
+//! ```
+//! use tonytools::texture::{get_pixel_size, get_scale_factor, get_total_size, Format};
+//!
+//! // TEXT files store a downscaled copy of large textures.
+//! assert_eq!(get_scale_factor(256, 256), 2);
+//! assert_eq!(get_scale_factor(64, 64), 1);
+//!
+//! // 256x256 DXT1 is 64x64 blocks of 8 bytes, the next mip is a quarter of that.
+//! assert_eq!(get_pixel_size(Format::DXT1, 256, 256, 0), 32768);
+//! assert_eq!(get_total_size(Format::DXT1, 256, 256, 2), 32768 + 8192);
+//! ```
 use std::cmp::{max, min};
 
 use num::ToPrimitive;
 
-use crate::hmtextures::Format;
+pub use crate::hmtextures::{Format, Type};
 
+/// Bits per pixel of the format, 0 for unknown formats.
 pub fn bits_per_pixel(format: Format) -> u32 {
     match format {
         Format::R16G16B16A16 => 64,
@@ -16,6 +29,8 @@ pub fn bits_per_pixel(format: Format) -> u32 {
     }
 }
 
+/// The factor TEXT dimensions are divided by when the full size texture is
+/// stored in a TEXD. Textures outside 2^15..=2^24 pixels aren't scaled.
 pub fn get_scale_factor(width: u32, height: u32) -> u32 {
     let area = (width * height) as f32;
     if (1 << 15) as f32 <= area && area <= (1 << 24) as f32 {
@@ -25,10 +40,12 @@ pub fn get_scale_factor(width: u32, height: u32) -> u32 {
     }
 }
 
+/// The number of mips a texture of this width can have, capped at 14.
 pub fn max_mip_count(width: u32, _: u32) -> u32 {
     min(1 + (width as f32).log2().floor() as u32, 0xE)
 }
 
+/// The width and height of a compression block, 1 for uncompressed formats.
 pub fn pixel_block_size(format: Format) -> u32 {
     match format {
         Format::DXT1 | Format::DXT5 | Format::BC4 | Format::BC5 | Format::BC7 => 4,
@@ -36,33 +53,42 @@ pub fn pixel_block_size(format: Format) -> u32 {
     }
 }
 
+/// The row pitch and slice size in bytes of a single mip. Sizes too big for a
+/// u32 saturate, so a bad header gives a size no data can match.
+///
+/// ```
+/// use tonytools::texture::{compute_pitch, Format};
+///
+/// assert_eq!(compute_pitch(Format::R8G8B8A8, 16, 16), (64, 1024));
+/// ```
 pub fn compute_pitch(format: Format, width: u32, height: u32) -> (u32, u32) {
     let pitch;
     let slice;
 
     match format {
         Format::DXT1 | Format::BC4 => {
-            let nbw = max(1, (width + 3) / 4);
-            let nbh = max(1, (height + 3) / 4);
-            pitch = nbw * 8;
-            slice = pitch * nbh;
+            let nbw = max(1, width.div_ceil(4));
+            let nbh = max(1, height.div_ceil(4));
+            pitch = nbw.saturating_mul(8);
+            slice = pitch.saturating_mul(nbh);
         }
         Format::DXT5 | Format::BC5 | Format::BC7 => {
-            let nbw = max(1, (width + 3) / 4);
-            let nbh = max(1, (height + 3) / 4);
-            pitch = nbw * 16;
-            slice = pitch * nbh;
+            let nbw = max(1, width.div_ceil(4));
+            let nbh = max(1, height.div_ceil(4));
+            pitch = nbw.saturating_mul(16);
+            slice = pitch.saturating_mul(nbh);
         }
         _ => {
             let bpp = bits_per_pixel(format);
-            pitch = (width * bpp + 7) / 8;
-            slice = pitch * height;
+            pitch = width.saturating_mul(bpp).div_ceil(8);
+            slice = pitch.saturating_mul(height);
         }
     }
 
     (pitch, slice)
 }
 
+/// The size in bytes of the given mip level, clamped to the last mip.
 pub fn get_pixel_size(format: Format, mut width: u32, mut height: u32, mut mip_level: u8) -> u32 {
     let max_mip = max_mip_count(width, height) as u8;
     if mip_level > max_mip {
@@ -77,11 +103,13 @@ pub fn get_pixel_size(format: Format, mut width: u32, mut height: u32, mut mip_l
     slice
 }
 
+/// The size in bytes of the first `mip_levels` mips, saturating like
+/// [`compute_pitch`].
 pub fn get_total_size(format: Format, width: u32, height: u32, mip_levels: u8) -> u32 {
     let mut size: u32 = 0;
 
     for i in 0..mip_levels {
-        size += get_pixel_size(format, width, height, i);
+        size = size.saturating_add(get_pixel_size(format, width, height, i));
     }
 
     size